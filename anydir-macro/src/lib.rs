@@ -1,24 +1,373 @@
+mod modules;
+mod validate;
+
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, LitStr};
+use quote::{format_ident, quote};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, Expr, ExprLit, Lit, LitStr, MetaNameValue, Token,
+};
 
 /// Embed a directory at compile time
+///
+/// Options can follow the path as `name = value` pairs:
+///
+/// - `hashes = true` computes the SHA-256 hash of every file during
+///   expansion, so `content_hash()` on its entries costs nothing at runtime.
+///   The macro then expands to an `anydir::CtDir` rather than a bare
+///   `include_dir::Dir`. Every file is read and hashed each time the
+///   invoking crate is compiled, which adds to build times for large
+///   directories.
+/// - `follow_symlinks = false` leaves symbolic links (and everything behind
+///   them) out of the embedded tree. By default symlinks are followed, as
+///   `include_dir!` does, and their targets are embedded as if they were
+///   regular files and directories. The root path itself is always followed.
+/// - `validate = "config/*.toml"` (or a list like `["*.json", "*.toml"]`)
+///   parses every embedded file matching one of the glob patterns during
+///   expansion, and fails the build if one isn't valid JSON or TOML. The
+///   format comes from the file's `.json` or `.toml` extension. Patterns
+///   match paths relative to the embedded directory, and `*` doesn't cross
+///   `/`, so use `**/*.json` to match at any depth.
+/// - `require = ["index.html", "config/app.toml"]` fails the build if any
+///   of the listed files, relative to the embedded directory, isn't there
+///   to be embedded.
 #[proc_macro]
 pub fn embed_dir(input: TokenStream) -> TokenStream {
-    // Parse the input as a string literal
-    let input = parse_macro_input!(input as LitStr);
-    let dir_path = input.value();
+    let args = parse_macro_input!(input as EmbedArgs);
+    let dir_path = args.path.value();
 
     // Generate a unique identifier for the static variable
     let var_name = sanitize_identifier(&dir_path);
 
     let var_ident = syn::Ident::new(&var_name, proc_macro2::Span::call_site());
 
-    // Generate the code
+    for required in &args.require {
+        let present = resolve_env(&dir_path)
+            .map(|root| is_embedded(&root, &required.value(), args.follow_symlinks));
+        match present {
+            Ok(true) => {}
+            Ok(false) => {
+                let msg = format!("required file `{}` is missing", required.value());
+                return syn::Error::new(required.span(), msg)
+                    .to_compile_error()
+                    .into();
+            }
+            Err(msg) => {
+                return syn::Error::new(args.path.span(), msg)
+                    .to_compile_error()
+                    .into()
+            }
+        }
+    }
+
+    if !args.validate.is_empty() {
+        let checked = resolve_env(&dir_path)
+            .and_then(|root| validate::check(&root, args.follow_symlinks, &args.validate));
+        if let Err(msg) = checked {
+            return syn::Error::new(args.path.span(), msg)
+                .to_compile_error()
+                .into();
+        }
+    }
+
+    // Built by hand rather than with `include_dir!`, whose expansion names
+    // `include_dir::` unqualified and has no way to skip symlinks.
+    let tree = match resolve_env(&dir_path)
+        .and_then(|root| expand_dir(&root, &root, args.follow_symlinks))
+    {
+        Ok(tree) => tree,
+        Err(msg) => {
+            return syn::Error::new(args.path.span(), msg)
+                .to_compile_error()
+                .into()
+        }
+    };
+    let dir = quote! {
+        static #var_ident: ::include_dir::Dir = #tree;
+    };
+
+    if !args.hashes {
+        return quote!({ #dir &#var_ident }).into();
+    }
+
+    let hashes =
+        match resolve_env(&dir_path).and_then(|root| hash_files(&root, args.follow_symlinks)) {
+            Ok(hashes) => hashes,
+            Err(msg) => {
+                return syn::Error::new(args.path.span(), msg)
+                    .to_compile_error()
+                    .into()
+            }
+        };
+    let hashes = hashes
+        .iter()
+        .map(|(path, hash)| quote!((#path, [#(#hash),*])));
+    let hashes_ident = format_ident!("{var_name}_HASHES");
+
+    let expanded = quote! {
+        {
+            #dir
+            static #hashes_ident: &[(&str, [u8; 32])] = &[#(#hashes),*];
+            ::anydir::CtDir::with_hashes(&#var_ident, #hashes_ident)
+        }
+    };
+
+    TokenStream::from(expanded)
+}
+
+/// The arguments to `embed_dir!`: a path followed by `name = value` options.
+struct EmbedArgs {
+    path: LitStr,
+    hashes: bool,
+    follow_symlinks: bool,
+    validate: Vec<glob::Pattern>,
+    require: Vec<LitStr>,
+}
+
+impl Parse for EmbedArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = EmbedArgs {
+            path: input.parse()?,
+            hashes: false,
+            follow_symlinks: true,
+            validate: Vec::new(),
+            require: Vec::new(),
+        };
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let option: MetaNameValue = input.parse()?;
+            let name = option.path.get_ident().map(|i| i.to_string());
+            match name.as_deref() {
+                Some("hashes") => args.hashes = parse_bool(&option.value)?,
+                Some("follow_symlinks") => args.follow_symlinks = parse_bool(&option.value)?,
+                Some("validate") => args.validate = validate::parse_patterns(&option.value)?,
+                Some("require") => args.require = parse_strings(&option.value)?,
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        option.path,
+                        "unknown `embed_dir!` option",
+                    ))
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+fn parse_bool(value: &Expr) -> syn::Result<bool> {
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Bool(b), ..
+        }) => Ok(b.value),
+        _ => Err(syn::Error::new_spanned(value, "expected `true` or `false`")),
+    }
+}
+
+fn parse_strings(value: &Expr) -> syn::Result<Vec<LitStr>> {
+    let Expr::Array(array) = value else {
+        return Err(syn::Error::new_spanned(value, "expected a list of strings"));
+    };
+    array
+        .elems
+        .iter()
+        .map(|elem| match elem {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(s), ..
+            }) => Ok(s.clone()),
+            _ => Err(syn::Error::new_spanned(elem, "expected a string")),
+        })
+        .collect()
+}
+
+/// Whether the file at `relative` under `root` ends up embedded.
+fn is_embedded(root: &Path, relative: &str, follow_symlinks: bool) -> bool {
+    let relative = Path::new(relative);
+    if !relative
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)))
+    {
+        return false;
+    }
+    if !follow_symlinks {
+        let mut path = root.to_path_buf();
+        for component in relative {
+            path.push(component);
+            if fs::symlink_metadata(&path).is_ok_and(|m| m.is_symlink()) {
+                return false;
+            }
+        }
+    }
+    root.join(relative).is_file()
+}
+
+/// Expands `$VAR` environment variables in a path the same way
+/// `include_dir!` does.
+fn resolve_env(raw: &str) -> Result<PathBuf, String> {
+    let mut resolved = String::new();
+    let mut rest = raw;
+    while let Some(dollar) = rest.find('$') {
+        resolved.push_str(&rest[..dollar]);
+        let tail = &rest[dollar + 1..];
+        let len = tail
+            .find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+            .unwrap_or(tail.len());
+        let var = &tail[..len];
+        let value = std::env::var(var).map_err(|_| format!("unable to resolve ${var}"))?;
+        resolved.push_str(&value);
+        rest = &tail[len..];
+    }
+    resolved.push_str(rest);
+    Ok(PathBuf::from(resolved))
+}
+
+/// Hashes every file under `root`, returning their paths (normalized like
+/// `include_dir!` does) sorted alongside their hashes.
+fn hash_files(root: &Path, follow_symlinks: bool) -> Result<Vec<(String, [u8; 32])>, String> {
+    let mut hashes = Vec::new();
+    for path in list_files(root, follow_symlinks)? {
+        let contents =
+            fs::read(&path).map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+        hashes.push((normalize_path(root, &path), Sha256::digest(contents).into()));
+    }
+    hashes.sort();
+    Ok(hashes)
+}
+
+/// Lists every file under `root`, recursively.
+fn list_files(root: &Path, follow_symlinks: bool) -> Result<Vec<PathBuf>, String> {
+    fn walk(dir: &Path, follow_symlinks: bool, out: &mut Vec<PathBuf>) -> Result<(), String> {
+        for path in read_dir(dir, follow_symlinks)? {
+            if path.is_dir() {
+                walk(&path, follow_symlinks, out)?;
+            } else if path.is_file() {
+                out.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(root, follow_symlinks, &mut files)?;
+    Ok(files)
+}
+
+/// Builds the `include_dir::Dir` expression `include_dir!` would produce for
+/// `dir`, leaving out symlinks unless `follow_symlinks` is set.
+fn expand_dir(
+    root: &Path,
+    dir: &Path,
+    follow_symlinks: bool,
+) -> Result<proc_macro2::TokenStream, String> {
+    let mut children = Vec::new();
+    for path in read_dir(dir, follow_symlinks)? {
+        if path.is_dir() {
+            let tokens = expand_dir(root, &path, follow_symlinks)?;
+            children.push(quote!(::include_dir::DirEntry::Dir(#tokens)));
+        } else if path.is_file() {
+            let file = expand_file(root, &path)?;
+            children.push(quote!(::include_dir::DirEntry::File(#file)));
+        }
+    }
+
+    let normalized = normalize_path(root, dir);
+    Ok(quote! {
+        ::include_dir::Dir::new(#normalized, {
+            const ENTRIES: &[::include_dir::DirEntry<'static>] = &[#(#children),*];
+            ENTRIES
+        })
+    })
+}
+
+/// Builds the `include_dir::File` expression `include_dir!` would produce
+/// for the file at `path`.
+fn expand_file(root: &Path, path: &Path) -> Result<proc_macro2::TokenStream, String> {
+    let normalized = normalize_path(root, path);
+    let abs = path
+        .canonicalize()
+        .map_err(|e| format!("unable to resolve {}: {e}", path.display()))?;
+    let contents = match abs.to_str() {
+        Some(abs) => quote!(::core::include_bytes!(#abs)),
+        None => {
+            let contents =
+                fs::read(path).map_err(|e| format!("unable to read {}: {e}", path.display()))?;
+            let literal = proc_macro2::Literal::byte_string(&contents);
+            quote!(#literal)
+        }
+    };
+    Ok(quote!(::include_dir::File::new(#normalized, #contents)))
+}
+
+/// Lists the children of `dir` in sorted order, leaving out symlinks unless
+/// `follow_symlinks` is set.
+fn read_dir(dir: &Path, follow_symlinks: bool) -> Result<Vec<PathBuf>, String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("unable to read {}: {e}", dir.display()))?;
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let is_symlink = entry.file_type().is_ok_and(|t| t.is_symlink());
+        if follow_symlinks || !is_symlink {
+            paths.push(entry.path());
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// Makes `path` relative to `root` with `/` separators, as `include_dir!`
+/// does.
+fn normalize_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative.to_string_lossy().replace('\\', "/")
+}
+
+/// Embed a directory at compile time as a tree of modules mirroring its
+/// subdirectories, with one `include_dir::File` static per file
+///
+/// `embed_dir_modules!(pub mod assets = "$CARGO_MANIFEST_DIR/assets")` turns
+/// `assets/css/app.css` into `assets::css::APP_CSS`, so renaming or deleting
+/// a file breaks the build wherever it's used. Directory names become
+/// `snake_case` modules and file names (extension included)
+/// `SCREAMING_SNAKE_CASE` statics; anything that isn't an ASCII letter or
+/// digit becomes `_`. Two names in one directory that come out the same
+/// are a compile error naming both files.
+#[proc_macro]
+pub fn embed_dir_modules(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as modules::ModulesArgs);
+    match modules::expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// Embed a directory at compile time, resolving its path relative to the
+/// directory of the source file invoking the macro, like `include_str!`
+#[proc_macro]
+pub fn embed_dir_relative(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as LitStr);
+
+    let (dir_path, tree) = match resolve_relative(&input.value())
+        .and_then(|root| Ok((root.clone(), expand_dir(&root, &root, true)?)))
+    {
+        Ok(resolved) => resolved,
+        Err(msg) => return syn::Error::new(input.span(), msg).to_compile_error().into(),
+    };
+    let dir_path = dir_path.to_string_lossy().into_owned();
+
+    let var_name = sanitize_identifier(&dir_path);
+    let var_ident = syn::Ident::new(&var_name, proc_macro2::Span::call_site());
+
     let expanded = quote! {
         {
-            use include_dir::include_dir;
-            static #var_ident: include_dir::Dir = include_dir!(#dir_path);
+            static #var_ident: ::include_dir::Dir = #tree;
             &#var_ident
         }
     };
@@ -26,6 +375,29 @@ pub fn embed_dir(input: TokenStream) -> TokenStream {
     TokenStream::from(expanded)
 }
 
+fn resolve_relative(relative: &str) -> Result<PathBuf, String> {
+    let source = proc_macro::Span::call_site()
+        .local_file()
+        .ok_or("cannot determine the source file invoking `embed_dir_relative!`")?;
+    // The compiler reports source paths relative to its working directory.
+    let source = std::env::current_dir()
+        .map_err(|e| format!("cannot determine the current directory: {e}"))?
+        .join(source);
+    let base = source.parent().unwrap_or(&source);
+
+    let dir = base.join(relative);
+    let dir = dir.canonicalize().map_err(|e| {
+        format!(
+            "cannot find `{relative}` relative to `{}`: {e}",
+            base.display()
+        )
+    })?;
+    if !dir.is_dir() {
+        return Err(format!("`{}` is not a directory", dir.display()));
+    }
+    Ok(dir)
+}
+
 fn sanitize_identifier(s: &str) -> String {
     let mut result = String::from("DIR_");
     for c in s.chars() {
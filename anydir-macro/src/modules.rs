@@ -0,0 +1,125 @@
+use crate::{expand_file, normalize_path, read_dir, resolve_env};
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use syn::{
+    parse::{Parse, ParseStream},
+    Ident, LitStr, Token, Visibility,
+};
+
+/// The arguments to `embed_dir_modules!`: `<vis> mod <name> = "<path>"`.
+pub struct ModulesArgs {
+    vis: Visibility,
+    name: Ident,
+    path: LitStr,
+}
+
+impl Parse for ModulesArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let vis = input.parse()?;
+        input.parse::<Token![mod]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let path = input.parse()?;
+        Ok(ModulesArgs { vis, name, path })
+    }
+}
+
+pub fn expand(args: &ModulesArgs) -> syn::Result<TokenStream> {
+    let error = |msg| syn::Error::new(args.path.span(), msg);
+    let root = resolve_env(&args.path.value()).map_err(error)?;
+    if !root.is_dir() {
+        return Err(error(format!("`{}` is not a directory", root.display())));
+    }
+    let body = expand_module(&root, &root).map_err(error)?;
+    let (vis, name) = (&args.vis, &args.name);
+    Ok(quote! {
+        #vis mod #name {
+            #body
+        }
+    })
+}
+
+/// Builds the items of the module for `dir`: a nested module per
+/// subdirectory and a static per file.
+fn expand_module(root: &Path, dir: &Path) -> Result<TokenStream, String> {
+    let mut items = Vec::new();
+    let mut modules: HashMap<String, PathBuf> = HashMap::new();
+    let mut statics: HashMap<String, PathBuf> = HashMap::new();
+    for path in read_dir(dir, true)? {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let (taken, ident) = if path.is_dir() {
+            (&mut modules, module_ident(&name))
+        } else if path.is_file() {
+            (&mut statics, static_ident(&name))
+        } else {
+            continue;
+        };
+        if let Some(other) = taken.insert(ident.clone(), path.clone()) {
+            return Err(format!(
+                "`{}` and `{}` would both be named `{ident}`",
+                normalize_path(root, &other),
+                normalize_path(root, &path)
+            ));
+        }
+        let ident = Ident::new(&ident, Span::call_site());
+
+        if path.is_dir() {
+            let body = expand_module(root, &path)?;
+            items.push(quote! {
+                pub mod #ident {
+                    #body
+                }
+            });
+        } else {
+            let file = expand_file(root, &path)?;
+            items.push(quote! {
+                pub static #ident: ::include_dir::File<'static> = #file;
+            });
+        }
+    }
+    Ok(quote!(#(#items)*))
+}
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl",
+    "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+fn module_ident(name: &str) -> String {
+    let ident = identifier(name, char::to_ascii_lowercase);
+    if KEYWORDS.contains(&ident.as_str()) {
+        ident + "_"
+    } else {
+        ident
+    }
+}
+
+fn static_ident(name: &str) -> String {
+    identifier(name, char::to_ascii_uppercase)
+}
+
+/// Replaces everything but ASCII letters and digits in `name` with `_`,
+/// making sure the result is a valid identifier.
+fn identifier(name: &str, case: fn(&char) -> char) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                case(&c)
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) || ident == "_" {
+        ident.insert(0, '_');
+    }
+    ident
+}
@@ -0,0 +1,54 @@
+use crate::{list_files, normalize_path};
+use glob::{MatchOptions, Pattern};
+use std::{fs, path::Path};
+use syn::{Expr, ExprArray, ExprLit, Lit};
+
+/// Parses the `validate` option: a glob pattern string, or an array of them.
+pub fn parse_patterns(value: &Expr) -> syn::Result<Vec<Pattern>> {
+    let parse = |expr: &Expr| match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        }) => Pattern::new(&s.value())
+            .map_err(|e| syn::Error::new_spanned(s, format!("invalid glob pattern: {e}"))),
+        _ => Err(syn::Error::new_spanned(
+            expr,
+            "expected a glob pattern string",
+        )),
+    };
+    match value {
+        Expr::Array(ExprArray { elems, .. }) => elems.iter().map(parse).collect(),
+        _ => Ok(vec![parse(value)?]),
+    }
+}
+
+/// Checks that every file under `root` matching one of `patterns` parses
+/// as JSON or TOML, going by its extension.
+pub fn check(root: &Path, follow_symlinks: bool, patterns: &[Pattern]) -> Result<(), String> {
+    let options = MatchOptions {
+        require_literal_separator: true,
+        ..MatchOptions::new()
+    };
+    for path in list_files(root, follow_symlinks)? {
+        let relative = normalize_path(root, &path);
+        if !patterns.iter().any(|p| p.matches_with(&relative, options)) {
+            continue;
+        }
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("unable to read {relative} for validation: {e}"))?;
+        let parsed = match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => serde_json::from_str::<serde_json::Value>(&contents)
+                .map(drop)
+                .map_err(|e| e.to_string()),
+            Some("toml") => toml::from_str::<toml::Table>(&contents)
+                .map(drop)
+                .map_err(|e| e.to_string()),
+            _ => {
+                return Err(format!(
+                    "{relative} matches a `validate` pattern but isn't a .json or .toml file"
+                ))
+            }
+        };
+        parsed.map_err(|e| format!("{relative} is not valid: {e}"))?;
+    }
+    Ok(())
+}
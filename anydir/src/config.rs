@@ -0,0 +1,68 @@
+use crate::FileEntry;
+use serde_json::Value;
+use std::io;
+
+/// Parses a config file into a generic value, picking the format from its
+/// extension.
+pub(crate) fn parse<F: FileEntry + ?Sized>(entry: &F) -> io::Result<Value> {
+    let path = entry.path();
+    let invalid = |e: &dyn std::fmt::Display| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid config in {}: {e}", path.display()),
+        )
+    };
+    let contents = entry.read_string()?;
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => serde_json::from_str(&contents).map_err(|e| invalid(&e)),
+        Some("toml") => toml::from_str(&contents).map_err(|e| invalid(&e)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a .json or .toml config file", path.display()),
+        )),
+    }
+}
+
+/// Merges `overlay` into `base`: tables are merged key by key, recursively,
+/// and any other value in `overlay` replaces the one in `base`.
+pub(crate) fn merge(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+pub(crate) fn deserialize<T: serde::de::DeserializeOwned>(value: Value) -> io::Result<T> {
+    serde_json::from_value(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[test]
+fn merge_layers() {
+    let mut base = serde_json::json!({
+        "name": "app",
+        "server": { "port": 80, "host": "localhost" },
+        "tags": ["a", "b"],
+    });
+    merge(
+        &mut base,
+        serde_json::json!({ "server": { "port": 8080 }, "tags": ["c"], "debug": true }),
+    );
+    assert_eq!(
+        base,
+        serde_json::json!({
+            "name": "app",
+            "server": { "port": 8080, "host": "localhost" },
+            "tags": ["c"],
+            "debug": true,
+        })
+    );
+}
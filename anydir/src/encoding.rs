@@ -0,0 +1,45 @@
+/// Precompressed encodings we know how to serve, with the extension of the
+/// sibling file holding each, in order of preference.
+const ENCODINGS: [(&str, &str); 2] = [("br", ".br"), ("gzip", ".gz")];
+
+/// Returns the entries of [`ENCODINGS`] accepted by an `Accept-Encoding`
+/// header value, best first.
+///
+/// Encodings are ranked by their `q` value, falling back to our own
+/// preference on ties. A `*` entry applies to encodings not named
+/// explicitly, and anything with `q=0` is excluded.
+pub(crate) fn preferred(accept_encoding: &str) -> Vec<(&'static str, &'static str)> {
+    let mut wildcard = None;
+    let mut named: Vec<(&str, f32)> = Vec::new();
+    for item in accept_encoding.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or("").trim();
+        let q = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if name == "*" {
+            wildcard = Some(q);
+        } else if !name.is_empty() {
+            named.push((name, q));
+        }
+    }
+
+    let mut accepted: Vec<_> = ENCODINGS
+        .iter()
+        .enumerate()
+        .filter_map(|(rank, &(encoding, ext))| {
+            let q = named
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(encoding))
+                .map(|&(_, q)| q)
+                .or(wildcard)?;
+            (q > 0.0).then_some((q, rank, encoding, ext))
+        })
+        .collect();
+    accepted.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    accepted
+        .into_iter()
+        .map(|(_, _, encoding, ext)| (encoding, ext))
+        .collect()
+}
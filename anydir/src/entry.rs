@@ -0,0 +1,1261 @@
+use crate::{
+    read::{HashingWriter, LazyFile, Records, SeekableRead, StreamOptions},
+    snapshot::hex,
+    AnyDir, CtDir, MemFileEntry, RtDir, RtOptions,
+};
+use include_dir::File;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File as FsFile},
+    io::{self, BufRead, BufReader, Read},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// A single file inside a [`DirOps`](crate::DirOps) directory.
+pub trait FileEntry {
+    /// The path of the file relative to the root of its directory.
+    fn path(&self) -> &Path;
+
+    /// The size of the file in bytes, read from metadata without touching
+    /// its contents.
+    fn len(&self) -> io::Result<u64>;
+
+    fn is_empty(&self) -> io::Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// The size of the file if it's known without any I/O: always for
+    /// compile-time and in-memory files, never for runtime ones, which
+    /// would need a `stat`. Use [`FileEntry::len`] when the size is needed
+    /// regardless.
+    fn size_hint(&self) -> Option<u64> {
+        None
+    }
+
+    /// Clears `buf` and reads the file's contents into it, returning the
+    /// number of bytes read. Reusing one buffer across many files avoids an
+    /// allocation per file.
+    fn read_bytes_into(&self, buf: &mut Vec<u8>) -> io::Result<usize>;
+
+    /// Clears `buf` and reads the file's contents into it as UTF-8, returning
+    /// the number of bytes read.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] naming the file if it isn't
+    /// valid UTF-8.
+    fn read_string_into(&self, buf: &mut String) -> io::Result<usize>;
+
+    /// Opens the file for streaming reads. Runtime files are buffered.
+    fn reader(&self) -> io::Result<Box<dyn Read + '_>>;
+
+    /// Opens the file for random access, for parsers that need to jump
+    /// around (zip central directories, font tables). Runtime files are
+    /// buffered; seeking in compile-time and in-memory files just moves a
+    /// cursor over their bytes.
+    fn seekable(&self) -> io::Result<Box<dyn SeekableRead + '_>>;
+
+    /// Like [`FileEntry::reader`], but runtime files are buffered with
+    /// `options.buffer_size` bytes instead of the default. See
+    /// [`StreamOptions`] for the tradeoffs.
+    fn reader_with_options(&self, options: StreamOptions) -> io::Result<Box<dyn Read + '_>> {
+        let _ = options;
+        self.reader()
+    }
+
+    fn read_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.read_bytes_into(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Reads the file's contents as UTF-8. See [`FileEntry::read_string_into`].
+    fn read_string(&self) -> io::Result<String> {
+        let mut buf = String::new();
+        self.read_string_into(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Reads at most the first `n` bytes of the file.
+    fn read_head(&self, n: usize) -> io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.reader()?.take(n as u64).read_to_end(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Detects whether the file's lines end in `\n` or `\r\n`, to preserve
+    /// or normalize them when rewriting it.
+    ///
+    /// Only the first [`LINE_ENDING_SCAN_LEN`] bytes (64 KiB) are read, so
+    /// a huge file whose style changes further in is reported by its
+    /// prefix. A lone `\r` is not a line ending.
+    fn detect_line_endings(&self) -> io::Result<LineEnding> {
+        Ok(LineEnding::detect(&self.read_head(LINE_ENDING_SCAN_LEN)?))
+    }
+
+    /// Like [`FileEntry::read_bytes`], but fails with
+    /// [`io::ErrorKind::TimedOut`] if the read takes longer than `timeout`,
+    /// so a hung network mount can't stall a request forever. The read runs
+    /// on tokio's blocking thread pool.
+    ///
+    /// A read that times out keeps going in the background until the
+    /// filesystem returns, holding on to a blocking thread. Files whose
+    /// contents are already in memory are returned at once, whatever the
+    /// timeout.
+    #[cfg(feature = "tokio")]
+    fn read_bytes_timeout(
+        &self,
+        timeout: std::time::Duration,
+    ) -> impl std::future::Future<Output = io::Result<Vec<u8>>> + Send
+    where
+        Self: Clone + Send + 'static,
+    {
+        let entry = self.clone();
+        async move {
+            if entry.size_hint().is_some() {
+                return entry.read_bytes();
+            }
+            let path = entry.path().to_path_buf();
+            let read = tokio::task::spawn_blocking(move || entry.read_bytes());
+            match tokio::time::timeout(timeout, read).await {
+                Ok(joined) => joined.map_err(io::Error::other)?,
+                Err(_) => Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("reading {} timed out after {timeout:?}", path.display()),
+                )),
+            }
+        }
+    }
+
+    /// Reads from the start of the file into `bufs`, filling them in order,
+    /// and returns the number of bytes read: all of the buffers' capacity
+    /// unless the file is shorter.
+    ///
+    /// Runtime files are read with the file's vectored read on tokio's
+    /// blocking thread pool. The borrowed buffers can't go to another
+    /// thread, so the data lands in scratch buffers of the same sizes first
+    /// and is copied across afterwards. For compile-time and in-memory
+    /// files nothing is read at all: the "vectored" read is a `memcpy` of
+    /// their bytes across the buffers, not true scatter I/O.
+    #[cfg(feature = "tokio")]
+    fn read_vectored_async(
+        &self,
+        bufs: &mut [io::IoSliceMut<'_>],
+    ) -> impl std::future::Future<Output = io::Result<usize>> + Send
+    where
+        Self: Sync,
+    {
+        async move {
+            let mut reader = self.reader()?;
+            let mut total = 0;
+            for buf in bufs.iter_mut() {
+                let mut filled = 0;
+                while filled < buf.len() {
+                    match reader.read(&mut buf[filled..]) {
+                        Ok(0) => return Ok(total + filled),
+                        Ok(n) => filled += n,
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                total += filled;
+            }
+            Ok(total)
+        }
+    }
+
+    /// Reads a UTF-8 preview of at most `max_bytes` of the file, without
+    /// reading the rest of it.
+    ///
+    /// If the file is longer, the preview is cut at the last character
+    /// boundary within `max_bytes` and [`PREVIEW_MARKER`] is appended (on
+    /// top of the `max_bytes`). Fails with [`io::ErrorKind::InvalidData`]
+    /// if the previewed bytes aren't valid UTF-8.
+    fn read_string_preview(&self, max_bytes: usize) -> io::Result<String> {
+        let mut head = self.read_head(max_bytes.saturating_add(1))?;
+        let truncated = head.len() > max_bytes;
+        head.truncate(max_bytes);
+        let valid = match std::str::from_utf8(&head) {
+            Ok(_) => head.len(),
+            // Not an encoding error, just a character cut off by the cap.
+            Err(e) if truncated && e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => return Err(invalid_utf8(self.path())),
+        };
+        head.truncate(valid);
+        let mut preview = String::from_utf8(head).map_err(|_| invalid_utf8(self.path()))?;
+        if truncated {
+            preview.push_str(PREVIEW_MARKER);
+        }
+        Ok(preview)
+    }
+
+    /// Computes the SHA-256 hash of the file's contents, streaming them
+    /// rather than reading the whole file into memory.
+    fn content_hash(&self) -> io::Result<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        io::copy(&mut self.reader()?, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+
+    /// Reads the file's contents and hashes them with `D` in the same pass,
+    /// e.g. to serve a file and compute its ETag without reading it twice.
+    /// Returns the contents and the digest.
+    ///
+    /// Compile-time and in-memory contents are copied and hashed straight
+    /// from memory.
+    fn read_and_hash<D: sha2::digest::Digest>(&self) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        let capacity = self.size_hint().unwrap_or(0) as usize;
+        let mut writer = HashingWriter::<_, D>::new(Vec::with_capacity(capacity));
+        io::copy(&mut self.reader()?, &mut writer)?;
+        let (contents, hash) = writer.finish();
+        Ok((contents, hash.to_vec()))
+    }
+
+    /// Like [`FileEntry::content_hash`], but the hash is computed once and
+    /// reused by later calls, so repeated comparisons don't re-read the
+    /// file. Tree-level operations like
+    /// [`DirOps::tree_hash`](crate::DirOps::tree_hash) go through it.
+    ///
+    /// The cache is shared by clones of the entry. [`MemDir`](crate::MemDir)
+    /// and `ZipDir` keep one per file, so it's also shared by every entry
+    /// they hand out for that file; a runtime directory's entries only
+    /// share it when reused by [`RtDir::cache_listing`].
+    ///
+    /// Runtime files are stamped with their size and modification time when
+    /// hashed, and hashed again once either changes. A rewrite that keeps
+    /// both, to the filesystem's timestamp precision, goes unnoticed; call
+    /// [`FileEntry::content_hash`] when that matters.
+    fn cached_hash(&self) -> io::Result<[u8; 32]>;
+
+    /// An HTTP `ETag` for the file, quotes included.
+    ///
+    /// Runtime files get a weak ETag (`W/"…"`) built from their size and
+    /// modification time, so computing it is a single `stat`. Compile-time
+    /// and in-memory files get a strong ETag holding their
+    /// [`FileEntry::cached_hash`], which reads the contents once per entry.
+    fn etag(&self) -> io::Result<String> {
+        Ok(format!("\"{}\"", hex(&self.cached_hash()?)))
+    }
+
+    /// Reads the file as a sequence of `record_size`-byte records.
+    ///
+    /// If the file's length isn't a multiple of `record_size`, the final
+    /// partial record is yielded as a shorter `Vec` when `allow_partial` is
+    /// set, and as an [`io::ErrorKind::UnexpectedEof`] error otherwise. The
+    /// iterator ends after any error. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if `record_size` is zero.
+    fn read_records(&self, record_size: usize, allow_partial: bool) -> io::Result<Records<'_>> {
+        if record_size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "record size must be non-zero",
+            ));
+        }
+        Ok(Records::new(self.reader()?, record_size, allow_partial))
+    }
+
+    /// Reads the file as segments separated by `delim`, e.g. `b'\0'` for
+    /// `find -print0` output, streaming it rather than reading it whole.
+    ///
+    /// Delimiters are not included in the segments. A delimiter at the very
+    /// end of the file ends the last segment rather than starting an empty
+    /// one, so `a\0b\0` and `a\0b` both give `a` and `b`; any other empty
+    /// segment, as between two adjacent delimiters, is yielded as an empty
+    /// `Vec`.
+    fn read_split(&self, delim: u8) -> io::Result<impl Iterator<Item = io::Result<Vec<u8>>> + '_> {
+        Ok(BufReader::new(self.reader()?).split(delim))
+    }
+
+    /// Computes the relative path leading from the directory containing
+    /// `other` to this file, e.g. `../css/app.css` from `js/app.js`, for
+    /// linking assets to each other.
+    ///
+    /// Returns `None` if the two paths have no root in common, which can
+    /// only happen once entries have been re-anchored (e.g. one path is
+    /// absolute and the other isn't), or if `other`'s path climbs out of its
+    /// root with `..`.
+    fn relative_to(&self, other: &impl FileEntry) -> Option<PathBuf> {
+        let target = self.path();
+        let base = other.path().parent().unwrap_or(Path::new(""));
+        if target.has_root() != base.has_root() {
+            return None;
+        }
+
+        let mut target = target.components().peekable();
+        let mut base = base.components().peekable();
+        while let (Some(a), Some(b)) = (target.peek(), base.peek()) {
+            if a != b {
+                break;
+            }
+            target.next();
+            base.next();
+        }
+
+        let mut relative = PathBuf::new();
+        for c in base {
+            match c {
+                Component::Normal(_) => relative.push(".."),
+                Component::CurDir => {}
+                _ => return None,
+            }
+        }
+        relative.extend(target);
+        Some(relative)
+    }
+
+    /// Deserializes the file as JSON or TOML, depending on its `.json` or
+    /// `.toml` extension.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] for other extensions, and
+    /// with [`io::ErrorKind::InvalidData`] naming the file if it can't be
+    /// parsed into a `T`.
+    #[cfg(feature = "config")]
+    fn read_config<T: serde::de::DeserializeOwned>(&self) -> io::Result<T> {
+        crate::config::deserialize(crate::config::parse(self)?)
+    }
+
+    /// Where the file lands when its directory is extracted to `dest`, e.g.
+    /// by [`DirOps::extract_with_manifest`](crate::DirOps::extract_with_manifest).
+    fn extracted_path(&self, dest: &Path) -> PathBuf {
+        dest.join(self.path())
+    }
+
+    /// Guesses the file's MIME type, like `text/css`, from its extension.
+    /// The contents aren't looked at.
+    #[cfg(feature = "mime")]
+    fn mime_type(&self) -> Option<&'static str> {
+        mime_guess::from_path(self.path()).first_raw()
+    }
+
+    /// Decodes the file as an image. The format comes from the extension,
+    /// or from the file's first bytes if the extension isn't recognized.
+    ///
+    /// Only BMP, GIF, ICO, JPEG, PNG and WebP decoders are enabled by
+    /// default; enable more through your own dependency on `image`. Fails
+    /// with [`io::ErrorKind::InvalidData`] naming the file if it can't be
+    /// decoded.
+    #[cfg(feature = "image")]
+    fn read_image(&self) -> io::Result<image::DynamicImage> {
+        let path = self.path();
+        let invalid = |e: image::ImageError| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unable to decode {} as an image: {e}", path.display()),
+            )
+        };
+        let bytes = self.read_bytes()?;
+        let format = match image::ImageFormat::from_path(path) {
+            Ok(format) => format,
+            Err(_) => image::guess_format(&bytes).map_err(invalid)?,
+        };
+        image::load_from_memory_with_format(&bytes, format).map_err(invalid)
+    }
+
+    /// Appends the file to a tar archive being built, under its relative
+    /// path, so entries from anydir can be mixed with other files in one
+    /// archive.
+    #[cfg(feature = "archive")]
+    fn append_to_tar<W: io::Write>(&self, builder: &mut tar::Builder<W>) -> io::Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(self.len()?);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, self.path(), self.reader()?)
+    }
+}
+
+/// Appended by [`FileEntry::read_string_preview`] to previews that don't
+/// cover the whole file.
+pub const PREVIEW_MARKER: &str = "…";
+
+/// How many bytes [`FileEntry::detect_line_endings`] scans.
+pub const LINE_ENDING_SCAN_LEN: usize = 64 * 1024;
+
+/// The line-ending style of a text file, returned by
+/// [`FileEntry::detect_line_endings`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    /// Every line ends in `\n`.
+    Lf,
+    /// Every line ends in `\r\n`.
+    CrLf,
+    /// Both styles appear.
+    Mixed,
+    /// There are no line breaks.
+    None,
+}
+
+impl LineEnding {
+    fn detect(bytes: &[u8]) -> LineEnding {
+        let (mut lf, mut crlf) = (false, false);
+        for (i, _) in bytes.iter().enumerate().filter(|&(_, &b)| b == b'\n') {
+            if i > 0 && bytes[i - 1] == b'\r' {
+                crlf = true;
+            } else {
+                lf = true;
+            }
+            if lf && crlf {
+                return LineEnding::Mixed;
+            }
+        }
+        match (lf, crlf) {
+            (true, _) => LineEnding::Lf,
+            (_, true) => LineEnding::CrLf,
+            _ => LineEnding::None,
+        }
+    }
+}
+
+/// Whether `path` is a plain relative path that stays inside the directory
+/// it's joined onto.
+pub(crate) fn is_contained(path: &Path) -> bool {
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+/// Whether `path` names something inside a directory: non-empty and made
+/// only of plain names, with no `.`, `..` or root.
+pub(crate) fn is_normal(path: &Path) -> bool {
+    path.components().next().is_some()
+        && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Parses a path written with `/` separators, as stored in blobs and
+/// snapshots, if it passes [`is_normal`]. Empty names are rejected rather
+/// than dropped, so `/etc` doesn't turn into `etc`.
+pub(crate) fn parse_slash_path(path: &str) -> Option<PathBuf> {
+    let names: Vec<_> = path.split('/').collect();
+    let path: PathBuf = names.iter().collect();
+    (!names.contains(&"") && is_normal(&path)).then_some(path)
+}
+
+pub(crate) fn invalid_utf8(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{} is not valid UTF-8", path.display()),
+    )
+}
+
+/// Lazily computed content hash of an entry, shared with its clones and,
+/// where the directory keeps one per file, every entry for the same file.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HashCache(Arc<OnceLock<[u8; 32]>>);
+
+impl HashCache {
+    pub(crate) fn get_or_compute(
+        &self,
+        compute: impl FnOnce() -> io::Result<[u8; 32]>,
+    ) -> io::Result<[u8; 32]> {
+        if let Some(hash) = self.0.get() {
+            return Ok(*hash);
+        }
+        let hash = compute()?;
+        Ok(*self.0.get_or_init(|| hash))
+    }
+}
+
+// Whether the hash has been computed yet doesn't make two entries different.
+impl PartialEq for HashCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CtFileEntry {
+    pub(crate) file: &'static File<'static>,
+    pub(crate) path: PathBuf,
+    hash: HashCache,
+    dir: CtDir,
+}
+
+impl CtFileEntry {
+    /// `hash` is the file's precomputed content hash, if it was embedded
+    /// with one. `dir` is the directory the entry was listed from.
+    pub(crate) fn new(file: &'static File<'static>, hash: Option<[u8; 32]>, dir: CtDir) -> Self {
+        CtFileEntry {
+            file,
+            path: dir.relative(file.path()).to_path_buf(),
+            hash: HashCache(Arc::new(hash.map(OnceLock::from).unwrap_or_default())),
+            dir,
+        }
+    }
+}
+
+impl FileEntry for CtFileEntry {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.file.contents().len() as u64)
+    }
+
+    fn size_hint(&self) -> Option<u64> {
+        Some(self.file.contents().len() as u64)
+    }
+
+    fn read_bytes_into(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        buf.clear();
+        buf.extend_from_slice(self.file.contents());
+        Ok(buf.len())
+    }
+
+    fn read_string_into(&self, buf: &mut String) -> io::Result<usize> {
+        let contents =
+            std::str::from_utf8(self.file.contents()).map_err(|_| invalid_utf8(&self.path))?;
+        buf.clear();
+        buf.push_str(contents);
+        Ok(buf.len())
+    }
+
+    fn reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(self.file.contents()))
+    }
+
+    fn seekable(&self) -> io::Result<Box<dyn SeekableRead + '_>> {
+        Ok(Box::new(io::Cursor::new(self.file.contents())))
+    }
+
+    /// Returns the hash computed at build time if the directory was embedded
+    /// with `hashes = true`, or one computed earlier by
+    /// [`FileEntry::cached_hash`]. Embedded contents never change, so a
+    /// cached hash is always current.
+    fn content_hash(&self) -> io::Result<[u8; 32]> {
+        match self.hash.0.get() {
+            Some(hash) => Ok(*hash),
+            None => Ok(Sha256::digest(self.file.contents()).into()),
+        }
+    }
+
+    fn cached_hash(&self) -> io::Result<[u8; 32]> {
+        self.hash.get_or_compute(|| self.content_hash())
+    }
+}
+
+/// A runtime file's content hash, with the size and modification time it
+/// had when hashed.
+type StampedHash = ((u64, SystemTime), [u8; 32]);
+
+/// The [`StampedHash`] of a runtime file, once computed, shared between
+/// clones of the entry.
+#[derive(Debug, Clone, Default)]
+struct StampedHashCache(Arc<Mutex<Option<StampedHash>>>);
+
+// Whether the hash has been computed yet doesn't make two entries different.
+impl PartialEq for StampedHashCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtFileEntry {
+    /// Where the file lives on disk.
+    pub(crate) full_path: PathBuf,
+    pub(crate) path: PathBuf,
+    hash: StampedHashCache,
+    options: RtOptions,
+}
+
+impl RtFileEntry {
+    pub(crate) fn new(full_path: PathBuf, path: PathBuf) -> Self {
+        RtFileEntry {
+            full_path,
+            path,
+            hash: StampedHashCache::default(),
+            options: RtOptions::default(),
+        }
+    }
+
+    /// Records the options of the [`RtDir`] the entry was listed from.
+    pub(crate) fn with_options(mut self, options: RtOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl FileEntry for RtFileEntry {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        Ok(fs::metadata(&self.full_path)?.len())
+    }
+
+    fn read_bytes_into(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        buf.clear();
+        FsFile::open(&self.full_path)?.read_to_end(buf)
+    }
+
+    fn read_string_into(&self, buf: &mut String) -> io::Result<usize> {
+        buf.clear();
+        FsFile::open(&self.full_path)?
+            .read_to_string(buf)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::InvalidData => invalid_utf8(&self.path),
+                _ => e,
+            })
+    }
+
+    fn reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(BufReader::new(FsFile::open(&self.full_path)?)))
+    }
+
+    fn reader_with_options(&self, options: StreamOptions) -> io::Result<Box<dyn Read + '_>> {
+        let file = FsFile::open(&self.full_path)?;
+        Ok(Box::new(BufReader::with_capacity(
+            options.buffer_size(),
+            file,
+        )))
+    }
+
+    fn seekable(&self) -> io::Result<Box<dyn SeekableRead + '_>> {
+        Ok(Box::new(BufReader::new(FsFile::open(&self.full_path)?)))
+    }
+
+    fn cached_hash(&self) -> io::Result<[u8; 32]> {
+        let meta = fs::metadata(&self.full_path)?;
+        let stamp = (meta.len(), meta.modified()?);
+        let cached = *self.hash.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cached_stamp, hash)) = cached {
+            if cached_stamp == stamp {
+                return Ok(hash);
+            }
+        }
+        // Stamped before reading, so a write racing with the read leaves a
+        // stamp that no longer matches and the file is hashed again.
+        let hash = self.content_hash()?;
+        *self.hash.0.lock().unwrap_or_else(|e| e.into_inner()) = Some((stamp, hash));
+        Ok(hash)
+    }
+
+    #[cfg(feature = "tokio")]
+    fn read_vectored_async(
+        &self,
+        bufs: &mut [io::IoSliceMut<'_>],
+    ) -> impl std::future::Future<Output = io::Result<usize>> + Send
+    where
+        Self: Sync,
+    {
+        let path = self.full_path.clone();
+        let lens: Vec<_> = bufs.iter().map(|buf| buf.len()).collect();
+        async move {
+            let read = tokio::task::spawn_blocking(move || {
+                let mut scratch: Vec<_> = lens.into_iter().map(|len| vec![0; len]).collect();
+                let mut slices: Vec<_> =
+                    scratch.iter_mut().map(|b| io::IoSliceMut::new(b)).collect();
+                let mut slices = &mut slices[..];
+                let mut file = FsFile::open(&path)?;
+                let mut total = 0;
+                while !slices.is_empty() {
+                    match file.read_vectored(slices) {
+                        Ok(0) => break,
+                        Ok(n) => {
+                            total += n;
+                            io::IoSliceMut::advance_slices(&mut slices, n);
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                Ok((scratch, total))
+            });
+            let (scratch, total) = read.await.map_err(io::Error::other)??;
+            let mut left = total;
+            for (buf, read) in bufs.iter_mut().zip(&scratch) {
+                let n = left.min(read.len());
+                buf[..n].copy_from_slice(&read[..n]);
+                left -= n;
+            }
+            Ok(total)
+        }
+    }
+
+    fn etag(&self) -> io::Result<String> {
+        let metadata = fs::metadata(&self.full_path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(format!(
+            "W/\"{:x}-{:x}\"",
+            metadata.len(),
+            modified.as_nanos()
+        ))
+    }
+}
+
+/// A file from any kind of directory.
+///
+/// Which variants exist depends on the crate's enabled features (`Zip`
+/// needs `zip`), and Cargo unifies features across the dependency graph, so
+/// matches outside this crate need a wildcard arm.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum AnyFileEntry {
+    Ct(CtFileEntry),
+    Rt(RtFileEntry),
+    Mem(MemFileEntry),
+    #[cfg(feature = "zip")]
+    Zip(crate::ZipFileEntry),
+}
+
+// Forwards a method call to whichever entry type `self` holds.
+macro_rules! dispatch {
+    ($self:expr, $e:ident => $body:expr) => {
+        match $self {
+            AnyFileEntry::Ct($e) => $body,
+            AnyFileEntry::Rt($e) => $body,
+            AnyFileEntry::Mem($e) => $body,
+            #[cfg(feature = "zip")]
+            AnyFileEntry::Zip($e) => $body,
+        }
+    };
+}
+
+impl AnyFileEntry {
+    pub(crate) fn path_mut(&mut self) -> &mut PathBuf {
+        dispatch!(self, e => &mut e.path)
+    }
+
+    /// The directory holding this file, for enumerating its neighbors.
+    ///
+    /// Whatever the backend, the directory's paths are relative to the
+    /// file's parent and its entries include files in subdirectories, as
+    /// with any directory. A runtime entry's directory is a new [`RtDir`]
+    /// with the same options as the one the entry came from (or the
+    /// defaults, for an entry that didn't come from one), and its own
+    /// listing cache if that had one. In-memory and zip entries have no
+    /// directory handle to return, so they give `None`.
+    pub fn sibling_dir(&self) -> Option<AnyDir> {
+        match self {
+            AnyFileEntry::Ct(e) => {
+                let parent = e.file.path().parent().unwrap_or(Path::new(""));
+                e.dir.subdir(parent).map(AnyDir::Ct)
+            }
+            AnyFileEntry::Rt(e) => e
+                .full_path
+                .parent()
+                .map(|parent| AnyDir::Rt(RtDir::with_options(parent, e.options))),
+            AnyFileEntry::Mem(_) => None,
+            #[cfg(feature = "zip")]
+            AnyFileEntry::Zip(_) => None,
+        }
+    }
+
+    /// When the file was last modified. Only runtime files record this.
+    pub(crate) fn modified(&self) -> io::Result<Option<SystemTime>> {
+        match self {
+            AnyFileEntry::Rt(e) => Ok(Some(fs::metadata(&e.full_path)?.modified()?)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Reads the file and builds the headers to serve it with, for
+    /// assembling a response in any framework built on the `http` crate.
+    ///
+    /// The headers are `Content-Type` (from [`FileEntry::mime_type`], or
+    /// `application/octet-stream`), `Content-Length`, `ETag` (from
+    /// [`FileEntry::etag`]) and, for runtime files, `Last-Modified`.
+    #[cfg(feature = "http")]
+    pub fn into_response_parts(&self) -> io::Result<(http::HeaderMap, Vec<u8>)> {
+        use http::header::{self, HeaderValue};
+
+        let mut headers = http::HeaderMap::new();
+        let mime = self.mime_type().unwrap_or("application/octet-stream");
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(mime));
+        let etag = HeaderValue::try_from(self.etag()?).map_err(io::Error::other)?;
+        headers.insert(header::ETAG, etag);
+        if let Some(modified) = self.modified()? {
+            let modified = HeaderValue::try_from(http_date(modified)).map_err(io::Error::other)?;
+            headers.insert(header::LAST_MODIFIED, modified);
+        }
+        let body = self.read_bytes()?;
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+        Ok((headers, body))
+    }
+
+    /// Turns the entry into a reader that owns everything it needs. Runtime
+    /// files aren't opened until the first read.
+    pub(crate) fn into_reader(self) -> Box<dyn Read + Send> {
+        match self {
+            AnyFileEntry::Ct(e) => Box::new(e.file.contents()),
+            AnyFileEntry::Rt(e) => Box::new(LazyFile::new(e.full_path)),
+            AnyFileEntry::Mem(e) => Box::new(io::Cursor::new(e.contents)),
+            #[cfg(feature = "zip")]
+            AnyFileEntry::Zip(e) => e.into_reader(),
+        }
+    }
+}
+
+/// Formats `time` as an HTTP date, like `Sun, 06 Nov 1994 08:49:37 GMT`.
+#[cfg(feature = "http")]
+fn http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+    // Howard Hinnant's `civil_from_days`, with eras of 400 years counted
+    // from 0000-03-01.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + yoe + u64::from(month <= 2);
+    format!(
+        "{}, {day:02} {} {year} {:02}:{:02}:{:02} GMT",
+        // 1970-01-01 was a Thursday.
+        WEEKDAYS[((days + 4) % 7) as usize],
+        MONTHS[month as usize - 1],
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+    )
+}
+
+impl FileEntry for AnyFileEntry {
+    fn path(&self) -> &Path {
+        dispatch!(self, e => e.path())
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        dispatch!(self, e => e.len())
+    }
+
+    fn size_hint(&self) -> Option<u64> {
+        dispatch!(self, e => e.size_hint())
+    }
+
+    fn read_bytes_into(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        dispatch!(self, e => e.read_bytes_into(buf))
+    }
+
+    fn read_string_into(&self, buf: &mut String) -> io::Result<usize> {
+        dispatch!(self, e => e.read_string_into(buf))
+    }
+
+    fn reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        dispatch!(self, e => e.reader())
+    }
+
+    fn reader_with_options(&self, options: StreamOptions) -> io::Result<Box<dyn Read + '_>> {
+        dispatch!(self, e => e.reader_with_options(options))
+    }
+
+    fn seekable(&self) -> io::Result<Box<dyn SeekableRead + '_>> {
+        dispatch!(self, e => e.seekable())
+    }
+
+    fn content_hash(&self) -> io::Result<[u8; 32]> {
+        dispatch!(self, e => e.content_hash())
+    }
+
+    fn cached_hash(&self) -> io::Result<[u8; 32]> {
+        dispatch!(self, e => e.cached_hash())
+    }
+
+    #[cfg(feature = "tokio")]
+    async fn read_vectored_async(&self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        dispatch!(self, e => e.read_vectored_async(bufs).await)
+    }
+
+    fn etag(&self) -> io::Result<String> {
+        dispatch!(self, e => e.etag())
+    }
+}
+
+/// Returns the longest directory prefix shared by the paths of all `entries`.
+///
+/// Returns `None` if `entries` is empty or the entries have no leading
+/// directory in common.
+pub fn common_root(entries: &[AnyFileEntry]) -> Option<PathBuf> {
+    let mut parents = entries
+        .iter()
+        .map(|e| e.path().parent().unwrap_or(Path::new("")));
+    let mut root: Vec<_> = parents.next()?.components().collect();
+    for parent in parents {
+        let shared = root
+            .iter()
+            .zip(parent.components())
+            .take_while(|(a, b)| *a == b)
+            .count();
+        root.truncate(shared);
+    }
+    if root.is_empty() {
+        None
+    } else {
+        Some(root.iter().collect())
+    }
+}
+
+/// Strips the [`common_root`] of `entries` from their paths and re-anchors
+/// them under `new_root`.
+///
+/// Only the relative paths change; every entry still reads from the same file.
+pub fn rebase_all<P: AsRef<Path>>(entries: &[AnyFileEntry], new_root: P) -> Vec<AnyFileEntry> {
+    let root = common_root(entries).unwrap_or_default();
+    entries
+        .iter()
+        .cloned()
+        .map(|mut entry| {
+            let path = entry.path_mut();
+            // Every path starts with `root`, so this can't fail.
+            let rebased = new_root
+                .as_ref()
+                .join(path.strip_prefix(&root).unwrap_or(path));
+            *path = rebased;
+            entry
+        })
+        .collect()
+}
+
+#[test]
+fn rebase() {
+    use crate::anydir;
+
+    let entries: Vec<_> = anydir!(ct, "$CARGO_MANIFEST_DIR")
+        .file_entries()
+        .into_iter()
+        .filter(|e| e.path().starts_with("src"))
+        .collect();
+    assert_eq!(common_root(&entries), Some(PathBuf::from("src")));
+
+    let rebased = rebase_all(&entries, "lib");
+    assert!(rebased
+        .iter()
+        .any(|e| e.path() == Path::new("lib/entry.rs")));
+    assert_eq!(common_root(&rebased), Some(PathBuf::from("lib")));
+}
+
+#[test]
+fn relative_to() {
+    let entries = rebase_all(
+        &crate::anydir!(ct, "$CARGO_MANIFEST_DIR").file_entries(),
+        "assets",
+    );
+    let get = |path: &str| {
+        entries
+            .iter()
+            .find(|e| e.path() == Path::new(path))
+            .unwrap()
+    };
+    let manifest = get("assets/Cargo.toml");
+    let lib = get("assets/src/lib.rs");
+    let entry = get("assets/src/entry.rs");
+
+    assert_eq!(manifest.relative_to(lib), Some("../Cargo.toml".into()));
+    assert_eq!(lib.relative_to(manifest), Some("src/lib.rs".into()));
+    assert_eq!(lib.relative_to(entry), Some("lib.rs".into()));
+
+    let elsewhere = rebase_all(std::slice::from_ref(lib), "/abs");
+    assert_eq!(elsewhere[0].relative_to(manifest), None);
+}
+
+#[test]
+fn read_string_preview() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "héllo").unwrap();
+    let entry = &crate::anydir!(rt, tmp.path()).file_entries()[0];
+
+    assert_eq!(entry.read_string_preview(10).unwrap(), "héllo");
+    assert_eq!(entry.read_string_preview(6).unwrap(), "héllo");
+    assert_eq!(entry.read_string_preview(4).unwrap(), "hél…");
+    // The cap falls in the middle of `é`.
+    assert_eq!(entry.read_string_preview(2).unwrap(), "h…");
+    assert_eq!(entry.read_string_preview(0).unwrap(), "…");
+}
+
+#[test]
+fn cached_hash() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a"), "before").unwrap();
+    let entry = crate::anydir!(rt, tmp.path()).file_entries().remove(0);
+    let before = entry.cached_hash().unwrap();
+    assert_eq!(before, entry.content_hash().unwrap());
+
+    // Clones share the cache, which holds while the file's size and mtime
+    // do, even though the contents changed underneath it.
+    let copy = entry.clone();
+    let modified = fs::metadata(tmp.path().join("a"))
+        .unwrap()
+        .modified()
+        .unwrap();
+    fs::write(tmp.path().join("a"), "BEFORE").unwrap();
+    FsFile::options()
+        .write(true)
+        .open(tmp.path().join("a"))
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    assert_eq!(copy.cached_hash().unwrap(), before);
+    assert_ne!(copy.content_hash().unwrap(), before);
+
+    fs::write(tmp.path().join("a"), "after").unwrap();
+    let after = entry.cached_hash().unwrap();
+    assert_ne!(after, before);
+    assert_eq!(after, entry.content_hash().unwrap());
+}
+
+#[test]
+fn read_string_into() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a.txt"), "hello").unwrap();
+    fs::write(tmp.path().join("b.bin"), [0xff, 0xfe]).unwrap();
+    let entries = crate::anydir!(rt, tmp.path()).file_entries();
+
+    let mut buf = String::from("stale");
+    assert_eq!(entries[0].read_string_into(&mut buf).unwrap(), 5);
+    assert_eq!(buf, "hello");
+
+    let err = entries[1].read_string_into(&mut buf).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("b.bin"));
+}
+
+#[test]
+fn read_split() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a"), b"one\0\0two\0").unwrap();
+    fs::write(tmp.path().join("b"), b"one,two").unwrap();
+    let entries = crate::anydir!(rt, tmp.path()).file_entries();
+
+    let split = |i: usize, delim| -> Vec<_> {
+        let segments = entries[i].read_split(delim).unwrap();
+        segments.map(Result::unwrap).collect()
+    };
+    assert_eq!(split(0, b'\0'), [&b"one"[..], b"", b"two"]);
+    assert_eq!(split(1, b','), [&b"one"[..], b"two"]);
+}
+
+#[test]
+fn read_records() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("data"), b"aabbc").unwrap();
+    let entry = &crate::anydir!(rt, tmp.path()).file_entries()[0];
+
+    let records: Vec<_> = entry.read_records(2, true).unwrap().collect();
+    let records: io::Result<Vec<_>> = records.into_iter().collect();
+    assert_eq!(
+        records.unwrap(),
+        [b"aa".to_vec(), b"bb".to_vec(), b"c".to_vec()]
+    );
+
+    let mut strict = entry.read_records(2, false).unwrap();
+    assert_eq!(strict.next().unwrap().unwrap(), b"aa");
+    assert_eq!(strict.next().unwrap().unwrap(), b"bb");
+    let err = strict.next().unwrap().unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    assert!(strict.next().is_none());
+
+    assert!(entry.read_records(0, true).is_err());
+}
+
+#[test]
+fn detect_line_endings() {
+    use crate::MemDir;
+
+    let mut dir = MemDir::new();
+    for (name, contents) in [
+        ("lf", "a\nb\n"),
+        ("crlf", "a\r\nb\r\n"),
+        ("mixed", "a\r\nb\n"),
+        ("none", "a\rb"),
+    ] {
+        dir.insert(name, contents.as_bytes());
+    }
+    let mut long = vec![b'a'; LINE_ENDING_SCAN_LEN];
+    long.extend_from_slice(b"\r\n");
+    dir.insert("long", long);
+
+    let detect = |name| dir.get_file(name).unwrap().detect_line_endings().unwrap();
+    assert_eq!(detect("lf"), LineEnding::Lf);
+    assert_eq!(detect("crlf"), LineEnding::CrLf);
+    assert_eq!(detect("mixed"), LineEnding::Mixed);
+    assert_eq!(detect("none"), LineEnding::None);
+    assert_eq!(detect("long"), LineEnding::None);
+}
+
+#[test]
+fn read_and_hash() {
+    use sha2::Sha512;
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("file"), "some contents").unwrap();
+    let entry = RtFileEntry::new(tmp.path().join("file"), "file".into());
+
+    let (contents, hash) = entry.read_and_hash::<Sha256>().unwrap();
+    assert_eq!(contents, b"some contents");
+    assert_eq!(hash, entry.content_hash().unwrap());
+    let (_, hash) = entry.read_and_hash::<Sha512>().unwrap();
+    assert_eq!(hash, Sha512::digest(b"some contents").to_vec());
+}
+
+#[test]
+fn seekable() {
+    use crate::anydir;
+    use std::io::{Seek, SeekFrom};
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("Cargo.toml"), "[package]").unwrap();
+    let ct = anydir!(ct, "$CARGO_MANIFEST_DIR").get_file("Cargo.toml");
+    let rt = anydir!(rt, tmp.path()).get_file("Cargo.toml");
+    for entry in [ct.unwrap(), rt.unwrap()] {
+        let mut file = entry.seekable().unwrap();
+        let mut buf = [0; 7];
+        file.seek(SeekFrom::Start(1)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"package");
+        file.seek(SeekFrom::Current(-3)).unwrap();
+        file.read_exact(&mut buf[..3]).unwrap();
+        assert_eq!(&buf[..3], b"age");
+    }
+}
+
+#[cfg(all(feature = "tokio", unix))]
+#[tokio::test]
+async fn read_bytes_timeout() {
+    use crate::anydir;
+    use std::{process::Command, time::Duration};
+
+    let embedded = anydir!(ct, "$CARGO_MANIFEST_DIR").get_file("Cargo.toml");
+    let embedded = embedded.unwrap().read_bytes_timeout(Duration::ZERO).await;
+    assert!(embedded.unwrap().starts_with(b"[package]"));
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("file"), "contents").unwrap();
+    // Opening a FIFO for reading blocks until something opens it for
+    // writing, like a hung mount would.
+    let fifo = tmp.path().join("fifo");
+    assert!(Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .unwrap()
+        .success());
+
+    let fast = RtFileEntry::new(tmp.path().join("file"), "file".into());
+    let fast = fast.read_bytes_timeout(Duration::from_secs(10)).await;
+    assert_eq!(fast.unwrap(), b"contents");
+
+    let slow = RtFileEntry::new(fifo.clone(), "fifo".into());
+    let err = slow
+        .read_bytes_timeout(Duration::from_millis(50))
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    // Unblock the abandoned read so the runtime can shut down.
+    drop(FsFile::options().write(true).open(&fifo).unwrap());
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn read_vectored_async() {
+    use crate::anydir;
+    use io::IoSliceMut;
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("file"), "short").unwrap();
+    let entries = [
+        anydir!(ct, "$CARGO_MANIFEST_DIR")
+            .get_file("Cargo.toml")
+            .unwrap(),
+        anydir!(rt, env!("CARGO_MANIFEST_DIR"))
+            .get_file("Cargo.toml")
+            .unwrap(),
+    ];
+    for entry in entries {
+        let (mut a, mut b) = ([0; 3], [0; 6]);
+        let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+        assert_eq!(entry.read_vectored_async(&mut bufs).await.unwrap(), 9);
+        assert_eq!((&a, &b), (b"[pa", b"ckage]"));
+    }
+
+    let entry = anydir!(rt, tmp.path()).get_file("file").unwrap();
+    let (mut a, mut b) = ([0; 2], [b'-'; 8]);
+    let mut bufs = [IoSliceMut::new(&mut a), IoSliceMut::new(&mut b)];
+    assert_eq!(entry.read_vectored_async(&mut bufs).await.unwrap(), 5);
+    assert_eq!((&a, &b), (b"sh", b"ort-----"));
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn into_response_parts() {
+    use crate::anydir;
+    use std::time::Duration;
+
+    assert_eq!(http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+    let time = UNIX_EPOCH + Duration::from_secs(784_111_777);
+    assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+    let time = UNIX_EPOCH + Duration::from_secs(951_825_600);
+    assert_eq!(http_date(time), "Tue, 29 Feb 2000 12:00:00 GMT");
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("app.css"), "body {}").unwrap();
+    let entry = anydir!(rt, tmp.path()).get_file("app.css").unwrap();
+    let (headers, body) = entry.into_response_parts().unwrap();
+    assert_eq!(body, b"body {}");
+    assert_eq!(headers["content-type"], "text/css");
+    assert_eq!(headers["content-length"], "7");
+    assert_eq!(headers["etag"], entry.etag().unwrap().as_str());
+    assert!(headers["last-modified"].to_str().unwrap().ends_with(" GMT"));
+
+    let entry = anydir!(ct, "$CARGO_MANIFEST_DIR").get_file("Cargo.toml");
+    let (headers, _) = entry.unwrap().into_response_parts().unwrap();
+    assert!(headers["etag"].to_str().unwrap().starts_with('"'));
+    assert!(!headers.contains_key("last-modified"));
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn read_image() {
+    use crate::MemDir;
+
+    let mut png = Vec::new();
+    image::RgbImage::new(3, 2)
+        .write_to(&mut io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+
+    let mut dir = MemDir::new();
+    dir.insert("logo.png", png.clone());
+    dir.insert("no_extension", png);
+    dir.insert("broken.png", &b"not a png"[..]);
+
+    for path in ["logo.png", "no_extension"] {
+        let image = dir.get_file(path).unwrap().read_image().unwrap();
+        assert_eq!((image.width(), image.height()), (3, 2));
+    }
+    let err = dir
+        .get_file("broken.png")
+        .unwrap()
+        .read_image()
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("broken.png"));
+}
+
+#[cfg(feature = "archive")]
+#[test]
+fn append_to_tar() {
+    let entry = crate::anydir!(ct, "$CARGO_MANIFEST_DIR")
+        .get_file("Cargo.toml")
+        .unwrap();
+    let mut builder = tar::Builder::new(Vec::new());
+    entry.append_to_tar(&mut builder).unwrap();
+    let archive = builder.into_inner().unwrap();
+
+    let mut archive = tar::Archive::new(&archive[..]);
+    let mut files = archive.entries().unwrap();
+    let mut file = files.next().unwrap().unwrap();
+    assert_eq!(file.path().unwrap(), Path::new("Cargo.toml"));
+    let mut contents = Vec::new();
+    file.read_to_end(&mut contents).unwrap();
+    assert_eq!(contents, entry.read_bytes().unwrap());
+    assert!(files.next().is_none());
+}
@@ -1,34 +1,1187 @@
-pub use anydir_macro::embed_dir;
-use include_dir::Dir;
-use std::{fs, path::PathBuf};
+// Lets `embed_dir!` expansions that name `::anydir` work inside this crate.
+extern crate self as anydir;
+
+#[cfg(feature = "config")]
+mod config;
+mod encoding;
+mod entry;
+mod listing;
+mod mem;
+#[cfg(feature = "http")]
+mod multipart;
+mod names;
+mod read;
+#[cfg(feature = "mime")]
+mod rich;
+mod snapshot;
+mod view;
+mod watch;
+#[cfg(feature = "zip")]
+mod zipdir;
+
+pub use anydir_macro::{embed_dir, embed_dir_modules, embed_dir_relative};
+pub use entry::{
+    common_root, rebase_all, AnyFileEntry, CtFileEntry, FileEntry, LineEnding, RtFileEntry,
+    LINE_ENDING_SCAN_LEN, PREVIEW_MARKER,
+};
+use include_dir::{Dir, DirEntry};
+use listing::ListingCache;
+pub use mem::{MemDir, MemFileEntry};
+use memmap2::Mmap;
+pub use names::{NamePolicy, NameViolation};
+use read::HashingWriter;
+pub use read::{Records, SeekableRead, StreamOptions};
+#[cfg(feature = "mime")]
+pub use rich::RichEntry;
+/// The `digest` crate, for naming hash functions in
+/// [`FileEntry::read_and_hash`].
+pub use sha2::digest;
+use sha2::Sha256;
+pub use snapshot::Snapshot;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, Read},
+    ops::Deref,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+pub use view::MappedPaths;
+pub use watch::{DirChange, DirState, PathKind};
+#[cfg(feature = "zip")]
+pub use zipdir::{ZipDir, ZipFileEntry};
 
 pub trait DirOps {
+    /// Returns the files directly inside the directory, not its
+    /// subdirectories, as paths relative to it.
     fn files(&self) -> Vec<PathBuf>;
+
+    /// Returns every file in the directory and its subdirectories, sorted by
+    /// path.
+    fn file_entries(&self) -> Vec<AnyFileEntry>;
+
+    /// Returns [`DirOps::file_entries`] numbered by their position in path
+    /// order, starting from `0`.
+    ///
+    /// The same tree always gets the same IDs, on any platform and in any
+    /// run, so they can stand in for paths in serialized indexes. Adding or
+    /// removing a file renumbers every file sorted after it.
+    fn enumerate_entries(&self) -> Vec<(u32, AnyFileEntry)> {
+        (0..).zip(self.file_entries()).collect()
+    }
+
+    /// Looks up a file by its path relative to the root of the directory.
+    ///
+    /// Paths that would escape the directory (absolute paths or ones
+    /// containing `..`) never match.
+    ///
+    /// The default searches [`DirOps::file_entries`], so implementations
+    /// that can look a path up directly should override it.
+    fn get_file<P: AsRef<Path>>(&self, path: P) -> Option<AnyFileEntry> {
+        let path = path.as_ref();
+        if !entry::is_contained(path) {
+            return None;
+        }
+        self.file_entries().into_iter().find(|e| e.path() == path)
+    }
+
+    /// Picks the best precompressed variant of `path` that a client sending
+    /// the given `Accept-Encoding` header can decode, returning it with the
+    /// `Content-Encoding` to serve it under.
+    ///
+    /// Variants are sibling files named `<path>.br` (served as `br`) and
+    /// `<path>.gz` (served as `gzip`), so they have to be generated and
+    /// embedded alongside the original. Brotli is preferred when both are
+    /// equally acceptable. Falls back to `path` itself with no encoding
+    /// (`None`), so no `Content-Encoding` header should be sent for it.
+    ///
+    /// Returns `None` if `path` doesn't exist, even when variants of it do,
+    /// so a client that accepts no encoding sees the same file as any other.
+    fn get_encoded(
+        &self,
+        path: &Path,
+        accept_encoding: &str,
+    ) -> Option<(AnyFileEntry, Option<&'static str>)> {
+        let original = self.get_file(path)?;
+        for (encoding, ext) in encoding::preferred(accept_encoding) {
+            let mut variant = path.as_os_str().to_owned();
+            variant.push(ext);
+            if let Some(entry) = self.get_file(variant) {
+                return Some((entry, Some(encoding)));
+            }
+        }
+        Some((original, None))
+    }
+
+    /// Returns [`DirOps::file_entries`] paired with each file's nesting
+    /// depth, counted as the number of directories in its relative path (so
+    /// `0` for files at the root).
+    fn entries_with_depth(&self) -> Vec<(AnyFileEntry, usize)> {
+        self.file_entries()
+            .into_iter()
+            .map(|entry| {
+                let depth = entry.path().components().count().saturating_sub(1);
+                (entry, depth)
+            })
+            .collect()
+    }
+
+    /// Applies a fallible `f` to every file in [`DirOps::file_entries`] order
+    /// and collects the results, stopping at the first error.
+    ///
+    /// ```no_run
+    /// # use anydir::{anydir, DirOps, FileEntry};
+    /// let lengths = anydir!(rt, "./config")
+    ///     .map_entries(|e| Ok(e.read_string()?.lines().count()))
+    ///     .unwrap();
+    /// ```
+    fn map_entries<T, F: Fn(&AnyFileEntry) -> io::Result<T>>(&self, f: F) -> io::Result<Vec<T>> {
+        self.file_entries().iter().map(f).collect()
+    }
+
+    /// Like [`DirOps::map_entries`], but runs `f` on rayon's thread pool.
+    /// Results stay in [`DirOps::file_entries`] order; if several files fail,
+    /// which error is returned is unspecified.
+    #[cfg(feature = "rayon")]
+    fn par_map_entries<T, F>(&self, f: F) -> io::Result<Vec<T>>
+    where
+        T: Send,
+        F: Fn(&AnyFileEntry) -> io::Result<T> + Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        self.file_entries().par_iter().map(f).collect()
+    }
+
+    /// A view of the directory whose entries have their relative paths
+    /// rewritten by `f`, e.g. to lowercase them or strip version suffixes.
+    /// See [`MappedPaths`] for how each method behaves on the view.
+    fn map_paths<F: Fn(&Path) -> PathBuf>(&self, f: F) -> MappedPaths<'_, Self, F> {
+        MappedPaths::new(self, f)
+    }
+
+    /// Counts the files in the directory tree for which `pred` returns
+    /// true, e.g. how many `.rs` files there are.
+    ///
+    /// Compile-time directories are counted without collecting their
+    /// entries into a `Vec`; other directories are listed as usual first.
+    fn count_matching<F: Fn(&AnyFileEntry) -> bool>(&self, pred: F) -> usize {
+        self.file_entries()
+            .iter()
+            .filter(|entry| pred(entry))
+            .count()
+    }
+
+    /// Returns the files whose MIME type has the top-level type `category`,
+    /// such as `"image"` or `"text"`, ignoring case.
+    ///
+    /// Types are guessed from extensions by [`FileEntry::mime_type`], so
+    /// files without a known extension never match.
+    #[cfg(feature = "mime")]
+    fn entries_of_type(&self, category: &str) -> io::Result<Vec<AnyFileEntry>> {
+        Ok(self
+            .file_entries()
+            .into_iter()
+            .filter(|entry| {
+                entry
+                    .mime_type()
+                    .and_then(|mime| mime.split('/').next())
+                    .is_some_and(|top| top.eq_ignore_ascii_case(category))
+            })
+            .collect())
+    }
+
+    /// Returns every file in the directory tree with its size, MIME type and
+    /// modification time, in [`DirOps::file_entries`] order: what a file
+    /// listing page or JSON endpoint needs.
+    ///
+    /// Runtime files cost one `stat` each; nothing else touches the
+    /// filesystem. With the `serde` feature, [`RichEntry`] implements
+    /// `Serialize`.
+    #[cfg(feature = "mime")]
+    fn rich_entries(&self) -> io::Result<Vec<RichEntry>> {
+        self.file_entries()
+            .into_iter()
+            .map(RichEntry::new)
+            .collect()
+    }
+
+    /// Encodes the whole directory tree as a `multipart/form-data` body,
+    /// for uploading it in one request. Returns the boundary, to send as
+    /// `Content-Type: multipart/form-data; boundary=<boundary>`, and the
+    /// body.
+    ///
+    /// Each file is one part whose field name and file name are its path
+    /// with `/` separators, and whose `Content-Type` is
+    /// [`FileEntry::mime_type`], or `application/octet-stream` if unknown.
+    /// The boundary is `anydir-` followed by 32 random-looking hex digits,
+    /// different on every call. The body is streamed: runtime files are only
+    /// opened as the body reaches them.
+    #[cfg(feature = "http")]
+    fn to_multipart(&self) -> io::Result<(String, impl Read + Send + 'static)> {
+        Ok(multipart::encode(self.file_entries()))
+    }
+
+    /// Pairs every file in the directory tree with a reader over its
+    /// contents, in [`DirOps::file_entries`] order, so each file can be
+    /// streamed through a hasher or uploader without looking it up again.
+    ///
+    /// Runtime files are opened on their first read and stay open until
+    /// their reader is dropped. Reading every file of a large tree before
+    /// dropping any of the readers can run out of file descriptors.
+    fn entries_with_readers(&self) -> io::Result<Vec<(PathBuf, Box<dyn Read + '_>)>> {
+        Ok(self
+            .file_entries()
+            .into_iter()
+            .map(|entry| {
+                let path = entry.path().to_path_buf();
+                let reader: Box<dyn Read> = entry.into_reader();
+                (path, reader)
+            })
+            .collect())
+    }
+
+    /// Reads every file in the directory tree as UTF-8 into a map keyed by
+    /// relative path, e.g. to load a registry of templates in one go.
+    ///
+    /// A file that isn't valid UTF-8, like a stray image, is left out of
+    /// the map if `skip_non_utf8` is set, and otherwise fails the whole call
+    /// with [`io::ErrorKind::InvalidData`] naming it. See
+    /// [`DirOps::read_all_strings_lossy`] to keep such files instead.
+    fn read_all_strings(&self, skip_non_utf8: bool) -> io::Result<HashMap<PathBuf, String>> {
+        let mut strings = HashMap::new();
+        for entry in self.file_entries() {
+            match String::from_utf8(entry.read_bytes()?) {
+                Ok(s) => {
+                    strings.insert(entry.path().to_path_buf(), s);
+                }
+                Err(_) if skip_non_utf8 => {}
+                Err(_) => return Err(entry::invalid_utf8(entry.path())),
+            }
+        }
+        Ok(strings)
+    }
+
+    /// Like [`DirOps::read_all_strings`], but invalid UTF-8 sequences are
+    /// replaced with `U+FFFD`, so every file is included.
+    fn read_all_strings_lossy(&self) -> io::Result<HashMap<PathBuf, String>> {
+        self.file_entries()
+            .into_iter()
+            .map(|entry| {
+                let bytes = entry.read_bytes()?;
+                let s = String::from_utf8_lossy(&bytes).into_owned();
+                Ok((entry.path().to_path_buf(), s))
+            })
+            .collect()
+    }
+
+    /// Returns the files whose contents start with `magic`, e.g.
+    /// `b"\x89PNG\r\n\x1a\n"` to find PNGs whatever their extension.
+    ///
+    /// Only the first `magic.len()` bytes of each file are read.
+    fn find_by_signature(&self, magic: &[u8]) -> io::Result<Vec<AnyFileEntry>> {
+        let mut found = Vec::new();
+        for entry in self.file_entries() {
+            if entry.read_head(magic.len())? == magic {
+                found.push(entry);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Folds every file into per-thread accumulators on rayon's thread pool,
+    /// then combines them with `reduce`, e.g. to total up sizes or build an
+    /// index of a large tree in parallel.
+    ///
+    /// `identity` creates the starting accumulator for each batch of files
+    /// and may be called any number of times. Fails with the first error
+    /// returned by `fold`, without waiting for the remaining files.
+    #[cfg(feature = "rayon")]
+    fn par_fold<T, F, R>(
+        &self,
+        identity: impl Fn() -> T + Send + Sync,
+        fold: F,
+        reduce: R,
+    ) -> io::Result<T>
+    where
+        T: Send,
+        F: Fn(T, &AnyFileEntry) -> io::Result<T> + Send + Sync,
+        R: Fn(T, T) -> T + Send + Sync,
+    {
+        use rayon::prelude::*;
+
+        self.file_entries()
+            .par_iter()
+            .try_fold(&identity, fold)
+            .try_reduce(&identity, |a, b| Ok(reduce(a, b)))
+    }
+
+    /// Reads every file in the directory tree concurrently on tokio's
+    /// blocking thread pool, with at most `max_concurrency` reads in flight
+    /// so large trees don't exhaust file descriptors. Results are in
+    /// [`DirOps::file_entries`] order.
+    ///
+    /// Files whose contents are already in memory (compile-time and
+    /// [`MemDir`] files) are copied immediately without spawning a task. The
+    /// directory itself is walked synchronously when this is called. A
+    /// `max_concurrency` of around 64 suits most systems; keep it well below
+    /// the process's open file limit (`ulimit -n`, often 1024). Fails with
+    /// [`io::ErrorKind::InvalidInput`] if `max_concurrency` is zero, or with
+    /// the first read error.
+    #[cfg(feature = "tokio")]
+    fn read_all_concurrent(
+        &self,
+        max_concurrency: usize,
+    ) -> impl std::future::Future<Output = io::Result<Vec<(AnyFileEntry, Vec<u8>)>>> + Send {
+        let entries = self.file_entries();
+        async move {
+            if max_concurrency == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "max_concurrency must be non-zero",
+                ));
+            }
+            let semaphore = Arc::new(tokio::sync::Semaphore::new(max_concurrency));
+            let mut results: Vec<Option<(AnyFileEntry, Vec<u8>)>> =
+                Vec::with_capacity(entries.len());
+            let mut tasks = tokio::task::JoinSet::<io::Result<_>>::new();
+            for (i, entry) in entries.into_iter().enumerate() {
+                if entry.size_hint().is_some() {
+                    let contents = entry.read_bytes()?;
+                    results.push(Some((entry, contents)));
+                    continue;
+                }
+                results.push(None);
+                let semaphore = semaphore.clone();
+                tasks.spawn(async move {
+                    let _permit = semaphore.acquire_owned().await.map_err(io::Error::other)?;
+                    tokio::task::spawn_blocking(move || {
+                        let contents = entry.read_bytes()?;
+                        Ok((i, entry, contents))
+                    })
+                    .await
+                    .map_err(io::Error::other)?
+                });
+            }
+            while let Some(result) = tasks.join_next().await {
+                let (i, entry, contents) = result.map_err(io::Error::other)??;
+                results[i] = Some((entry, contents));
+            }
+            Ok(results.into_iter().flatten().collect())
+        }
+    }
+
+    /// Serializes every file in the directory tree, with its path, into a
+    /// single self-contained blob that [`MemDir::from_blob`] loads back.
+    ///
+    /// See [`MemDir::from_blob`] for the format. Fails with
+    /// [`io::ErrorKind::InvalidData`] if a path isn't valid UTF-8.
+    fn to_blob(&self) -> io::Result<Vec<u8>> {
+        MemDir::write_blob(&self.file_entries())
+    }
+
+    /// Reads the config files at `paths`, in order, and deep-merges them
+    /// into one `T`, so an embedded file of defaults can be followed by
+    /// files that override some of its settings. Each file is JSON or TOML
+    /// depending on its extension; the formats can be mixed.
+    ///
+    /// Tables are merged key by key, so a later file only needs the keys it
+    /// changes. Any other value, including an array, replaces the earlier
+    /// one outright. Fails with [`io::ErrorKind::NotFound`] if a file is
+    /// missing, or as [`FileEntry::read_config`] does.
+    #[cfg(feature = "config")]
+    fn read_config_layered<T: serde::de::DeserializeOwned>(
+        &self,
+        paths: &[&Path],
+    ) -> io::Result<T> {
+        let mut merged = serde_json::Value::Object(Default::default());
+        for path in paths {
+            let entry = self.get_file(path).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("{} not found", path.display()),
+                )
+            })?;
+            config::merge(&mut merged, config::parse(&entry)?);
+        }
+        config::deserialize(merged)
+    }
+
+    /// Returns the files in the directory tree whose relative paths match
+    /// the glob `pattern`, in [`DirOps::file_entries`] order.
+    ///
+    /// `*` and `?` never match a `/`, so `*.css` only matches files at the
+    /// root; use `**/*.css` for every level. Fails with
+    /// [`io::ErrorKind::InvalidInput`] if `pattern` isn't a valid glob.
+    fn glob(&self, pattern: &str) -> io::Result<Vec<AnyFileEntry>> {
+        let matches = glob_matcher(pattern)?;
+        Ok(self
+            .file_entries()
+            .into_iter()
+            .filter(|entry| matches(entry.path()))
+            .collect())
+    }
+
+    /// Returns the files in the directory tree that [`DirOps::glob`] with
+    /// the same `pattern` leaves out, e.g. to find assets no processing
+    /// rule covers. Together the two split the tree exactly.
+    fn unmatched_by(&self, pattern: &str) -> io::Result<Vec<AnyFileEntry>> {
+        let matches = glob_matcher(pattern)?;
+        Ok(self
+            .file_entries()
+            .into_iter()
+            .filter(|entry| !matches(entry.path()))
+            .collect())
+    }
+
+    /// Returns the paths in `required` that aren't files in the directory
+    /// tree, in the order given, e.g. to check at startup that every
+    /// template a program needs is there.
+    fn missing_from(&self, required: &[&Path]) -> Vec<PathBuf> {
+        let entries = self.file_entries();
+        let present: HashSet<&Path> = entries.iter().map(|e| e.path()).collect();
+        required
+            .iter()
+            .filter(|path| !present.contains(**path))
+            .map(|path| path.to_path_buf())
+            .collect()
+    }
+
+    /// Returns the files in the directory tree that aren't in `expected`,
+    /// sorted by path. Together with [`DirOps::missing_from`], this checks
+    /// that a directory holds exactly the files a program expects.
+    fn extra_beyond(&self, expected: &[&Path]) -> Vec<PathBuf> {
+        let expected: HashSet<&Path> = expected.iter().copied().collect();
+        self.file_entries()
+            .iter()
+            .map(|e| e.path())
+            .filter(|path| !expected.contains(path))
+            .map(Path::to_path_buf)
+            .collect()
+    }
+
+    /// Asserts that the files in the directory tree are exactly `expected`,
+    /// for tests of generated or extracted directories.
+    ///
+    /// Paths are written with `/` separators, in any order; `.` components
+    /// are ignored, so `./a/b` matches `a/b`.
+    ///
+    /// # Panics
+    ///
+    /// If the paths don't match, with a message listing both the expected
+    /// paths that are missing and the files nobody expected.
+    #[track_caller]
+    fn assert_paths(&self, expected: &[&str]) {
+        let expected: Vec<PathBuf> = expected
+            .iter()
+            .map(|path| {
+                Path::new(path)
+                    .components()
+                    .filter(|c| !matches!(c, Component::CurDir))
+                    .collect()
+            })
+            .collect();
+        let expected: Vec<&Path> = expected.iter().map(PathBuf::as_path).collect();
+        let missing = self.missing_from(&expected);
+        let mut unexpected = self.extra_beyond(&expected);
+        if missing.is_empty() && unexpected.is_empty() {
+            return;
+        }
+        unexpected.sort();
+        let list = |paths: &[PathBuf]| -> String {
+            match paths {
+                [] => "\n    (none)".into(),
+                _ => paths
+                    .iter()
+                    .map(|path| format!("\n    {}", path.display()))
+                    .collect(),
+            }
+        };
+        panic!(
+            "directory paths don't match\n  missing:{}\n  unexpected:{}",
+            list(&missing),
+            list(&unexpected)
+        );
+    }
+
+    /// Writes every file in the directory tree under `dest`, creating
+    /// directories as needed, and returns each file's relative path with the
+    /// SHA-256 hash of what was written, in [`DirOps::file_entries`] order.
+    ///
+    /// Each file is hashed as it's copied, so verifying a deployment doesn't
+    /// need a second pass over the extracted files. Existing files are
+    /// overwritten.
+    fn extract_with_manifest<P: AsRef<Path>>(
+        &self,
+        dest: P,
+    ) -> io::Result<Vec<(PathBuf, [u8; 32])>> {
+        self.extract_with_manifest_options(dest, StreamOptions::default())
+    }
+
+    /// Like [`DirOps::extract_with_manifest`], but files are read and
+    /// written in chunks of `options.buffer_size` bytes. See
+    /// [`StreamOptions`] for the tradeoffs.
+    fn extract_with_manifest_options<P: AsRef<Path>>(
+        &self,
+        dest: P,
+        options: StreamOptions,
+    ) -> io::Result<Vec<(PathBuf, [u8; 32])>> {
+        let dest = dest.as_ref();
+        let mut manifest = Vec::new();
+        for entry in self.file_entries() {
+            let path = entry.path();
+            if !entry::is_contained(path) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is outside the directory", path.display()),
+                ));
+            }
+            let target = dest.join(path);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut writer = HashingWriter::<_, Sha256>::new(File::create(&target)?);
+            read::copy(
+                &mut entry.reader_with_options(options)?,
+                &mut writer,
+                options,
+            )?;
+            let (_, hash) = writer.finish();
+            manifest.push((path.to_path_buf(), hash.into()));
+        }
+        Ok(manifest)
+    }
+
+    /// Returns the files in the directory tree whose paths aren't valid
+    /// UTF-8, so they can be reported before anything that needs a `str`
+    /// (like building URLs) runs into them.
+    ///
+    /// Only runtime directories on Unix can hold such names. The rest of
+    /// the crate handles them as `OsStr`s, but methods that must write a
+    /// path as text either fail on them ([`DirOps::to_blob`]) or write them
+    /// lossily ([`Snapshot::write_to`]).
+    fn non_utf8_names(&self) -> Vec<PathBuf> {
+        self.file_entries()
+            .iter()
+            .map(|entry| entry.path())
+            .filter(|path| path.to_str().is_none())
+            .map(Path::to_path_buf)
+            .collect()
+    }
+
+    /// Checks every path in the directory tree for names that can't be
+    /// created on some filesystems, so extracting the tree elsewhere doesn't
+    /// fail halfway through. `policy` selects the checks to run.
+    ///
+    /// A path may be reported more than once if it has several problems.
+    fn validate_names(&self, policy: NamePolicy) -> Vec<(PathBuf, NameViolation)> {
+        names::validate(&self.file_entries(), &policy)
+    }
+
+    /// Records the content hash of every file in the directory tree.
+    ///
+    /// This reads every file in full unless its hash is already cached; see
+    /// [`FileEntry::cached_hash`].
+    fn snapshot(&self) -> io::Result<Snapshot> {
+        Snapshot::capture(self)
+    }
+
+    /// A Merkle hash of the paths and contents of every file in the
+    /// directory tree. See [`Snapshot::tree_hash`].
+    ///
+    /// This reads every file in full unless its hash is already cached; see
+    /// [`FileEntry::cached_hash`].
+    fn tree_hash(&self) -> io::Result<[u8; 32]> {
+        Ok(self.snapshot()?.tree_hash())
+    }
+
+    /// Whether `other` holds exactly the same paths with the same contents,
+    /// going by [`DirOps::tree_hash`]. Useful for a CI check that embedded
+    /// assets match their source.
+    ///
+    /// A `false` result doesn't say what differs; use
+    /// [`DirOps::changed_since_snapshot`] and
+    /// [`DirOps::removed_since_snapshot`] against `other`'s snapshot for
+    /// that.
+    fn same_tree_as(&self, other: &impl DirOps) -> io::Result<bool> {
+        Ok(self.tree_hash()? == other.tree_hash()?)
+    }
+
+    /// Returns the files whose contents differ from what `snap` recorded,
+    /// including files that weren't in the directory when it was taken.
+    ///
+    /// Files removed since `snap` was taken are reported by
+    /// [`DirOps::removed_since_snapshot`].
+    fn changed_since_snapshot(&self, snap: &Snapshot) -> io::Result<Vec<AnyFileEntry>> {
+        let mut changed = Vec::new();
+        for entry in self.file_entries() {
+            if snap.get(entry.path()) != Some(&entry.cached_hash()?) {
+                changed.push(entry);
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Returns the paths recorded in `snap` that are no longer in the
+    /// directory.
+    fn removed_since_snapshot(&self, snap: &Snapshot) -> Vec<PathBuf> {
+        let present: HashSet<_> = self
+            .file_entries()
+            .into_iter()
+            .map(|e| e.path().to_path_buf())
+            .collect();
+        snap.iter()
+            .map(|(path, _)| path)
+            .filter(|path| !present.contains(*path))
+            .map(Path::to_path_buf)
+            .collect()
+    }
+
+    /// Checks the directory tree against a known-good `manifest`, e.g. one
+    /// shipped alongside a runtime asset directory and loaded with
+    /// [`Snapshot::read_from`], and returns every path that doesn't match,
+    /// sorted: files whose contents hash differently, files the manifest
+    /// doesn't list, and listed files that are missing. An empty result
+    /// means the tree is exactly what the manifest describes.
+    ///
+    /// This reads every file in full, ignoring any cached hashes, since a
+    /// file rewritten without changing its size or mtime would otherwise
+    /// pass. A compile-time directory always matches a manifest captured
+    /// from it at build time.
+    fn verify_against(&self, manifest: &Snapshot) -> io::Result<Vec<PathBuf>> {
+        let entries = self.file_entries();
+        let mut mismatched = Vec::new();
+        for entry in &entries {
+            if manifest.get(entry.path()) != Some(&entry.content_hash()?) {
+                mismatched.push(entry.path().to_path_buf());
+            }
+        }
+        let present: HashSet<_> = entries.iter().map(|e| e.path()).collect();
+        mismatched.extend(
+            manifest
+                .iter()
+                .map(|(path, _)| path)
+                .filter(|path| !present.contains(*path))
+                .map(Path::to_path_buf),
+        );
+        mismatched.sort();
+        Ok(mismatched)
+    }
+
+    /// Reads this directory tree and `other` into one in-memory directory.
+    ///
+    /// Files are matched by relative path. A path in only one of the trees
+    /// is copied as is. For a path in both, `on_conflict` is called with the
+    /// path, this directory's contents and `other`'s contents, and whatever
+    /// it returns becomes the merged file, so it can pick either side,
+    /// concatenate them or merge them some other way. It's called for every
+    /// shared path, even when both sides hold the same bytes.
+    ///
+    /// Every file in both trees is read in full and held in memory.
+    fn merge_into_mem(
+        &self,
+        other: &impl DirOps,
+        on_conflict: impl Fn(&Path, &[u8], &[u8]) -> Vec<u8>,
+    ) -> io::Result<MemDir> {
+        let mut merged: BTreeMap<PathBuf, Vec<u8>> = BTreeMap::new();
+        for entry in self.file_entries() {
+            merged.insert(entry.path().to_path_buf(), entry.read_bytes()?);
+        }
+        for entry in other.file_entries() {
+            let theirs = entry.read_bytes()?;
+            let contents = match merged.get(entry.path()) {
+                Some(ours) => on_conflict(entry.path(), ours, &theirs),
+                None => theirs,
+            };
+            merged.insert(entry.path().to_path_buf(), contents);
+        }
+        let mut dir = MemDir::new();
+        for (path, contents) in merged {
+            dir.insert(path, contents);
+        }
+        Ok(dir)
+    }
+
+    /// Returns every file in the directory tree ordered by modification
+    /// time, newest or oldest first, e.g. for a "recently changed" listing.
+    /// Runtime files cost one `stat` each.
+    ///
+    /// Compile-time and in-memory files have no modification time, so they
+    /// keep their [`DirOps::file_entries`] path order, as do files modified
+    /// at the same instant.
+    fn entries_by_mtime(&self, newest_first: bool) -> io::Result<Vec<AnyFileEntry>> {
+        let mut keyed = self
+            .file_entries()
+            .into_iter()
+            .map(|entry| Ok((entry.modified()?, entry)))
+            .collect::<io::Result<Vec<_>>>()?;
+        keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) if newest_first => b.cmp(a),
+            (Some(a), Some(b)) => a.cmp(b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+        Ok(keyed.into_iter().map(|(_, entry)| entry).collect())
+    }
+
+    /// Computes the [`FileEntry::etag`] of every file in the directory tree,
+    /// keyed by relative path, so a server can build its conditional-request
+    /// table once at startup instead of per request.
+    ///
+    /// For runtime directories this is one `stat` per file. Compile-time
+    /// and in-memory files are hashed, so the map costs a full read of every
+    /// file: for large embedded trees, expect startup to take about as long
+    /// as hashing the whole embedded payload.
+    fn etag_map(&self) -> io::Result<HashMap<PathBuf, String>> {
+        self.file_entries()
+            .into_iter()
+            .map(|entry| Ok((entry.path().to_path_buf(), entry.etag()?)))
+            .collect()
+    }
+
+    /// Returns the `n` largest files in the directory tree with their sizes,
+    /// biggest first.
+    ///
+    /// Only file metadata is read, never contents. Files of equal size keep
+    /// their path order.
+    fn largest_files(&self, n: usize) -> io::Result<Vec<(AnyFileEntry, u64)>> {
+        let entries = self.file_entries();
+        // Min-heap of the `n` biggest sizes seen so far, so the smallest of
+        // them is the one evicted.
+        let mut heap = BinaryHeap::with_capacity(n + 1);
+        for (i, entry) in entries.iter().enumerate() {
+            heap.push(Reverse((entry.len()?, Reverse(i))));
+            if heap.len() > n {
+                heap.pop();
+            }
+        }
+        Ok(heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((len, Reverse(i)))| (entries[i].clone(), len))
+            .collect())
+    }
+
+    /// Times reading every file in the directory tree, in
+    /// [`DirOps::file_entries`] order, to find the files that make loading a
+    /// runtime directory slow (e.g. on a network mount).
+    ///
+    /// This reads the full contents of every file, streaming them into
+    /// nothing, so it costs as much I/O as loading the whole tree; it's for
+    /// diagnosis, not for production paths. Compile-time and in-memory
+    /// files report close to zero. Stops at the first file that can't be
+    /// read.
+    fn profile_reads(&self) -> io::Result<Vec<(AnyFileEntry, Duration)>> {
+        self.file_entries()
+            .into_iter()
+            .map(|entry| {
+                let start = Instant::now();
+                io::copy(&mut entry.reader()?, &mut io::sink())?;
+                Ok((entry, start.elapsed()))
+            })
+            .collect()
+    }
+
+    /// Maps every file in the directory once, keyed by the same paths as
+    /// [`DirOps::files`].
+    ///
+    /// Runtime files are memory-mapped so later reads are served from the
+    /// page cache without copying into the heap; compile-time files borrow
+    /// their embedded bytes directly.
+    ///
+    /// A mapping reflects the file as it is on disk, not as it was when
+    /// mapped: if a runtime file is modified while mapped its contents may
+    /// change underneath you, and truncating it can cause a `SIGBUS` on
+    /// access. Only use this on directories that aren't written to while
+    /// the map is alive.
+    ///
+    /// The default picks those files out of [`DirOps::file_entries`],
+    /// mapping runtime ones and reading anything else into memory.
+    fn mmap_all(&self) -> io::Result<HashMap<PathBuf, MappedFile>> {
+        let files: HashSet<_> = self.files().into_iter().collect();
+        let mut map = HashMap::new();
+        for entry in self.file_entries() {
+            if !files.contains(entry.path()) {
+                continue;
+            }
+            let contents = match &entry {
+                AnyFileEntry::Ct(e) => MappedFile::Static(e.file.contents()),
+                AnyFileEntry::Rt(e) => {
+                    let file = File::open(&e.full_path)?;
+                    // SAFETY: the mapping is only sound while nothing else
+                    // modifies the file, which is the caller's
+                    // responsibility (see above).
+                    MappedFile::Mmap(unsafe { Mmap::map(&file)? })
+                }
+                _ => MappedFile::Shared(entry.read_bytes()?.into()),
+            };
+            map.insert(entry.path().to_path_buf(), contents);
+        }
+        Ok(map)
+    }
+}
+
+/// Parses `pattern` into the predicate shared by [`DirOps::glob`] and
+/// [`DirOps::unmatched_by`], so the two always agree.
+fn glob_matcher(pattern: &str) -> io::Result<impl Fn(&Path) -> bool> {
+    let pattern =
+        glob::Pattern::new(pattern).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..glob::MatchOptions::new()
+    };
+    Ok(move |path: &Path| pattern.matches_path_with(path, options))
+}
+
+/// The contents of a file returned by [`DirOps::mmap_all`].
+#[derive(Debug)]
+pub enum MappedFile {
+    Static(&'static [u8]),
+    Mmap(Mmap),
+    Shared(Arc<[u8]>),
 }
 
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            MappedFile::Static(s) => s,
+            MappedFile::Mmap(m) => m,
+            MappedFile::Shared(s) => s,
+        }
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Build-time SHA-256 hashes of every file in a [`CtDir`], sorted by path.
+pub type FileHashes = [(&'static str, [u8; 32])];
+
+/// A directory embedded in the binary at compile time.
 #[derive(Debug, Clone, PartialEq)]
 pub struct CtDir {
     pub dir: &'static Dir<'static>,
+    hashes: Option<&'static FileHashes>,
+    /// What entry paths are relative to: the embedded root, as `include_dir`
+    /// records them, except for a [`AnyFileEntry::sibling_dir`].
+    root: &'static Path,
+}
+
+impl CtDir {
+    pub fn new(dir: &'static Dir<'static>) -> Self {
+        CtDir {
+            dir,
+            hashes: None,
+            root: Path::new(""),
+        }
+    }
+
+    /// Creates a `CtDir` whose entries return the given precomputed hashes
+    /// from [`FileEntry::content_hash`]. `hashes` must be sorted by path.
+    ///
+    /// This is what `embed_dir!(path, hashes = true)` expands to.
+    pub fn with_hashes(dir: &'static Dir<'static>, hashes: &'static FileHashes) -> Self {
+        CtDir {
+            dir,
+            hashes: Some(hashes),
+            root: Path::new(""),
+        }
+    }
+
+    fn entry(&self, file: &'static include_dir::File<'static>) -> AnyFileEntry {
+        let hash = self.hashes.and_then(|hashes| {
+            let path = file.path().to_str()?;
+            let i = hashes.binary_search_by(|(p, _)| (*p).cmp(path)).ok()?;
+            Some(hashes[i].1)
+        });
+        AnyFileEntry::Ct(CtFileEntry::new(file, hash, self.clone()))
+    }
+
+    /// Calls `f` on every embedded file, recursively, in path order.
+    fn for_each_file(&self, f: &mut impl FnMut(&'static include_dir::File<'static>)) {
+        fn walk(
+            dir: &'static Dir<'static>,
+            f: &mut impl FnMut(&'static include_dir::File<'static>),
+        ) {
+            for entry in dir.entries() {
+                match entry {
+                    DirEntry::Dir(d) => walk(d, f),
+                    DirEntry::File(file) => f(file),
+                }
+            }
+        }
+        walk(self.dir, f)
+    }
+
+    /// The embedded directory at `path`, which may be this directory itself,
+    /// sharing this directory's hashes. Unlike this one, its entry paths are
+    /// relative to the subdirectory, like an [`RtDir`] opened there.
+    fn subdir(&self, path: &Path) -> Option<CtDir> {
+        let dir = if self.dir.path() == path {
+            self.dir
+        } else {
+            self.dir.get_dir(path)?
+        };
+        Some(CtDir {
+            dir,
+            hashes: self.hashes,
+            root: dir.path(),
+        })
+    }
+
+    /// `path`, as `include_dir` records it, relative to `root`.
+    fn relative(&self, path: &'static Path) -> &'static Path {
+        path.strip_prefix(self.root).unwrap_or(path)
+    }
+}
+
+impl From<&'static Dir<'static>> for CtDir {
+    fn from(dir: &'static Dir<'static>) -> Self {
+        CtDir::new(dir)
+    }
 }
 
 impl DirOps for CtDir {
     fn files(&self) -> Vec<PathBuf> {
-        self.dir.files().map(|f| f.path().to_path_buf()).collect()
+        self.dir
+            .files()
+            .map(|f| self.relative(f.path()).to_path_buf())
+            .collect()
+    }
+
+    fn mmap_all(&self) -> io::Result<HashMap<PathBuf, MappedFile>> {
+        Ok(self
+            .dir
+            .files()
+            .map(|f| {
+                let path = self.relative(f.path()).to_path_buf();
+                (path, MappedFile::Static(f.contents()))
+            })
+            .collect())
+    }
+
+    fn file_entries(&self) -> Vec<AnyFileEntry> {
+        // Counting embedded files is cheap, and saves regrowing the `Vec`
+        // for trees with thousands of them.
+        let mut count = 0;
+        self.for_each_file(&mut |_| count += 1);
+        let mut entries = Vec::with_capacity(count);
+        self.for_each_file(&mut |file| entries.push(self.entry(file)));
+        entries
+    }
+
+    fn count_matching<F: Fn(&AnyFileEntry) -> bool>(&self, pred: F) -> usize {
+        let mut count = 0;
+        self.for_each_file(&mut |file| count += usize::from(pred(&self.entry(file))));
+        count
+    }
+
+    fn get_file<P: AsRef<Path>>(&self, path: P) -> Option<AnyFileEntry> {
+        let path = path.as_ref();
+        if !entry::is_contained(path) {
+            return None;
+        }
+        self.dir
+            .get_file(self.root.join(path))
+            .map(|f| self.entry(f))
     }
 }
 
+/// A directory read from the filesystem at runtime.
+///
+/// If `dir` points at a file rather than a directory, the `RtDir` behaves
+/// as a directory containing just that file, under its file name. This
+/// means a path that may be either can be used as-is.
 #[derive(Debug, Clone, PartialEq)]
 pub struct RtDir {
     pub dir: PathBuf,
+    listing: Option<ListingCache>,
+    include_hidden: bool,
+}
+
+/// The [`RtDir`] options an entry was listed with, which
+/// [`AnyFileEntry::sibling_dir`] carries over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RtOptions {
+    include_hidden: bool,
+    cache_listing: bool,
+}
+
+impl Default for RtOptions {
+    fn default() -> Self {
+        RtOptions {
+            include_hidden: true,
+            cache_listing: false,
+        }
+    }
+}
+
+impl RtDir {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        RtDir {
+            dir: dir.into(),
+            listing: None,
+            include_hidden: true,
+        }
+    }
+
+    /// Whether files and directories whose names start with `.`, like
+    /// `.git` or `.DS_Store`, are part of the directory. They are by
+    /// default. Excluded entries are skipped by every method, including
+    /// [`DirOps::get_file`], and excluded directories aren't walked.
+    ///
+    /// A [`CtDir`] holds whatever `include_dir` embedded, which includes
+    /// dotfiles.
+    pub fn include_hidden(mut self, include: bool) -> Self {
+        self.include_hidden = include;
+        // A listing cached with the old setting no longer applies.
+        if self.listing.is_some() {
+            self.listing = Some(ListingCache::default());
+        }
+        self
+    }
+
+    /// Caches the result of [`DirOps::file_entries`] instead of walking the
+    /// directory on every call.
+    ///
+    /// The cached listing is reused for as long as the modification time of
+    /// every directory in the tree is unchanged, which costs one `stat` per
+    /// directory rather than a full `read_dir` walk. Only the listing is
+    /// cached, never file contents.
+    ///
+    /// Directory mtimes only change when entries are added, removed or
+    /// renamed, and only as precisely as the filesystem records them, so a
+    /// change made within the same timestamp tick as the previous scan can
+    /// go unnoticed. Call [`RtDir::invalidate`] to force a rescan when that
+    /// matters. Clones of this `RtDir` share the same cache.
+    pub fn cache_listing(mut self, enabled: bool) -> Self {
+        self.listing = enabled.then(ListingCache::default);
+        self
+    }
+
+    /// Drops the cached listing, if any, so the next
+    /// [`DirOps::file_entries`] call rescans the directory.
+    pub fn invalidate(&self) {
+        if let Some(listing) = &self.listing {
+            listing.clear();
+        }
+    }
+
+    /// The options this directory was built with.
+    fn options(&self) -> RtOptions {
+        RtOptions {
+            include_hidden: self.include_hidden,
+            cache_listing: self.listing.is_some(),
+        }
+    }
+
+    /// A directory at `dir` built with `options`.
+    fn with_options(dir: &Path, options: RtOptions) -> RtDir {
+        RtDir::new(dir)
+            .include_hidden(options.include_hidden)
+            .cache_listing(options.cache_listing)
+    }
+
+    fn entry(&self, full_path: PathBuf, path: PathBuf) -> AnyFileEntry {
+        AnyFileEntry::Rt(RtFileEntry::new(full_path, path).with_options(self.options()))
+    }
+
+    /// The file name of `dir` if it points at a single file.
+    fn single_file(&self) -> Option<&OsStr> {
+        self.dir.file_name().filter(|_| self.dir.is_file())
+    }
+
+    /// Whether an entry named `name` is excluded by
+    /// [`RtDir::include_hidden`].
+    fn is_excluded(&self, name: &OsStr) -> bool {
+        !self.include_hidden && name.as_encoded_bytes().starts_with(b".")
+    }
+
+    /// Where the file at the relative `path` lives on disk.
+    fn full_path(&self, path: &Path) -> Option<PathBuf> {
+        match self.single_file() {
+            Some(name) => (path == Path::new(name)).then(|| self.dir.clone()),
+            None => Some(self.dir.join(path)),
+        }
+    }
+
+    /// Walks the whole tree, recording the mtime of every directory visited
+    /// into `dirs` if given.
+    fn scan(&self, mut dirs: Option<&mut Vec<(PathBuf, SystemTime)>>) -> Vec<AnyFileEntry> {
+        if let Some(name) = self.single_file() {
+            if let Some(dirs) = dirs {
+                // Lets a cached listing notice the file being removed.
+                if let Ok(modified) = fs::metadata(&self.dir).and_then(|m| m.modified()) {
+                    dirs.push((self.dir.clone(), modified));
+                }
+            }
+            if self.is_excluded(name) {
+                return Vec::new();
+            }
+            return vec![self.entry(self.dir.clone(), name.into())];
+        }
+
+        fn walk(
+            rt: &RtDir,
+            dir: &Path,
+            out: &mut Vec<AnyFileEntry>,
+            dirs: &mut Option<&mut Vec<(PathBuf, SystemTime)>>,
+        ) {
+            let Ok(entries) = fs::read_dir(dir) else {
+                return;
+            };
+            if let Some(dirs) = dirs {
+                if let Ok(modified) = fs::metadata(dir).and_then(|m| m.modified()) {
+                    dirs.push((dir.to_path_buf(), modified));
+                }
+            }
+            for entry in entries.flatten() {
+                if rt.is_excluded(&entry.file_name()) {
+                    continue;
+                }
+                let full_path = entry.path();
+                match entry.file_type() {
+                    Ok(ft) if ft.is_dir() => walk(rt, &full_path, out, dirs),
+                    Ok(ft) if ft.is_file() => {
+                        let path = full_path
+                            .strip_prefix(&rt.dir)
+                            .unwrap_or(&full_path)
+                            .to_path_buf();
+                        out.push(rt.entry(full_path, path));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut entries = Vec::new();
+        walk(self, &self.dir, &mut entries, &mut dirs);
+        entries.sort_by(|a, b| a.path().cmp(b.path()));
+        entries
+    }
 }
 
 impl DirOps for RtDir {
     fn files(&self) -> Vec<PathBuf> {
-        if let Ok(entries) = fs::read_dir(&self.dir) {
+        if let Some(name) = self.single_file() {
+            if self.is_excluded(name) {
+                Vec::new()
+            } else {
+                vec![name.into()]
+            }
+        } else if let Ok(entries) = fs::read_dir(&self.dir) {
             entries
                 .flatten()
+                .filter(|entry| !self.is_excluded(&entry.file_name()))
                 .filter_map(|entry| match entry.file_type() {
-                    Ok(ft) if ft.is_file() => Some(entry.path()),
+                    Ok(ft) if ft.is_file() => Some(entry.file_name().into()),
                     _ => None,
                 })
                 .collect()
@@ -36,6 +1189,37 @@ impl DirOps for RtDir {
             Vec::new()
         }
     }
+
+    fn mmap_all(&self) -> io::Result<HashMap<PathBuf, MappedFile>> {
+        let mut map = HashMap::new();
+        for path in self.files() {
+            let file = File::open(self.full_path(&path).unwrap_or_default())?;
+            // SAFETY: the mapping is only sound while nothing else modifies
+            // the file, which is the caller's responsibility (see `mmap_all`).
+            let mmap = unsafe { Mmap::map(&file)? };
+            map.insert(path, MappedFile::Mmap(mmap));
+        }
+        Ok(map)
+    }
+
+    fn file_entries(&self) -> Vec<AnyFileEntry> {
+        match &self.listing {
+            Some(listing) => listing.get_or_scan(|dirs| self.scan(Some(dirs))),
+            None => self.scan(None),
+        }
+    }
+
+    fn get_file<P: AsRef<Path>>(&self, path: P) -> Option<AnyFileEntry> {
+        let path = path.as_ref();
+        if !entry::is_contained(path) || path.iter().any(|name| self.is_excluded(name)) {
+            return None;
+        }
+        let full_path = self.full_path(path)?;
+        if !full_path.is_file() {
+            return None;
+        }
+        Some(self.entry(full_path, path.to_path_buf()))
+    }
 }
 
 pub enum AnyDir {
@@ -50,24 +1234,107 @@ impl DirOps for AnyDir {
             AnyDir::Rt(r) => r.files(),
         }
     }
+
+    fn mmap_all(&self) -> io::Result<HashMap<PathBuf, MappedFile>> {
+        match self {
+            AnyDir::Ct(c) => c.mmap_all(),
+            AnyDir::Rt(r) => r.mmap_all(),
+        }
+    }
+
+    fn file_entries(&self) -> Vec<AnyFileEntry> {
+        match self {
+            AnyDir::Ct(c) => c.file_entries(),
+            AnyDir::Rt(r) => r.file_entries(),
+        }
+    }
+
+    fn get_file<P: AsRef<Path>>(&self, path: P) -> Option<AnyFileEntry> {
+        match self {
+            AnyDir::Ct(c) => c.get_file(path),
+            AnyDir::Rt(r) => r.get_file(path),
+        }
+    }
+
+    fn count_matching<F: Fn(&AnyFileEntry) -> bool>(&self, pred: F) -> usize {
+        match self {
+            AnyDir::Ct(c) => c.count_matching(pred),
+            AnyDir::Rt(r) => r.count_matching(pred),
+        }
+    }
 }
 
+// The most common `DirOps` methods are also exposed as inherent methods, so
+// they can be called without importing the trait.
+macro_rules! inherent_dir_ops {
+    ($($ty:ty),*) => {$(
+        impl $ty {
+            /// See [`DirOps::files`].
+            pub fn files(&self) -> Vec<PathBuf> {
+                DirOps::files(self)
+            }
+
+            /// See [`DirOps::file_entries`].
+            pub fn file_entries(&self) -> Vec<AnyFileEntry> {
+                DirOps::file_entries(self)
+            }
+
+            /// See [`DirOps::get_file`].
+            pub fn get_file<P: AsRef<Path>>(&self, path: P) -> Option<AnyFileEntry> {
+                DirOps::get_file(self, path)
+            }
+        }
+    )*};
+}
+
+inherent_dir_ops!(CtDir, RtDir, AnyDir, MemDir);
+#[cfg(feature = "zip")]
+inherent_dir_ops!(ZipDir);
+
 pub fn anydir_rt<P: Into<std::path::PathBuf>>(path: P) -> AnyDir {
-    AnyDir::Rt(RtDir { dir: path.into() })
+    AnyDir::Rt(RtDir::new(path))
 }
 
 #[macro_export]
 macro_rules! anydir {
-    (ct, $path:literal) => {
-        $crate::AnyDir::Ct($crate::CtDir {
-            dir: embed_dir!($path),
-        })
+    (ct, $path:literal $(, $($options:tt)*)?) => {
+        $crate::AnyDir::Ct($crate::CtDir::from(
+            $crate::embed_dir!($path $(, $($options)*)?),
+        ))
     };
     (rt, $path:expr) => {
         $crate::anydir_rt($path)
     };
 }
 
+#[test]
+fn inherent_methods() {
+    // Deliberately doesn't import `DirOps`.
+    mod no_trait {
+        use crate::{FileEntry, MemDir};
+
+        pub fn files() -> Vec<std::path::PathBuf> {
+            crate::anydir_rt(env!("CARGO_MANIFEST_DIR")).files()
+        }
+
+        pub fn get_file() -> bool {
+            crate::anydir_rt(env!("CARGO_MANIFEST_DIR"))
+                .get_file("Cargo.toml")
+                .is_some()
+        }
+
+        pub fn mem() -> usize {
+            let mut dir = MemDir::new();
+            dir.insert("a/b", &b"contents"[..]);
+            let entry = dir.get_file("a/b").unwrap();
+            dir.file_entries().len() + entry.read_bytes().unwrap().len()
+        }
+    }
+    assert!(no_trait::files().contains(&PathBuf::from("Cargo.toml")));
+    assert!(no_trait::get_file());
+    assert_eq!(no_trait::mem(), 9);
+}
+
 #[test]
 fn files() {
     let dir = anydir!(ct, "$CARGO_MANIFEST_DIR");
@@ -89,3 +1356,932 @@ fn files() {
     }
     assert_eq!(files, files2);
 }
+
+#[test]
+fn mmap_all() {
+    let ct = anydir!(ct, "$CARGO_MANIFEST_DIR").mmap_all().unwrap();
+    let rt = anydir!(rt, env!("CARGO_MANIFEST_DIR")).mmap_all().unwrap();
+    assert_eq!(ct.len(), rt.len());
+    for (path, contents) in &ct {
+        assert_eq!(&contents[..], &rt[path][..]);
+    }
+}
+
+#[test]
+fn default_mmap_all() {
+    struct Wrapped(RtDir);
+
+    impl DirOps for Wrapped {
+        fn files(&self) -> Vec<PathBuf> {
+            self.0.files()
+        }
+
+        fn file_entries(&self) -> Vec<AnyFileEntry> {
+            self.0.file_entries()
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a"), "alpha").unwrap();
+    fs::create_dir(tmp.path().join("sub")).unwrap();
+    fs::write(tmp.path().join("sub/b"), "beta").unwrap();
+    let map = Wrapped(RtDir::new(tmp.path())).mmap_all().unwrap();
+    assert_eq!(map.len(), 1);
+    assert_eq!(&map[Path::new("a")][..], b"alpha");
+}
+
+#[test]
+fn default_get_file() {
+    struct Wrapped(RtDir);
+
+    impl DirOps for Wrapped {
+        fn files(&self) -> Vec<PathBuf> {
+            self.0.files()
+        }
+
+        fn file_entries(&self) -> Vec<AnyFileEntry> {
+            self.0.file_entries()
+        }
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir(tmp.path().join("sub")).unwrap();
+    fs::write(tmp.path().join("sub/b"), "beta").unwrap();
+    let dir = Wrapped(RtDir::new(tmp.path()));
+    let entry = dir.get_file("sub/b").unwrap();
+    assert_eq!(entry.read_bytes().unwrap(), b"beta");
+    assert!(dir.get_file("sub").is_none());
+    assert!(dir.get_file("sub/../sub/b").is_none());
+}
+
+#[test]
+fn embed_dir_relative() {
+    let dir = CtDir::new(embed_dir_relative!("."));
+    assert!(dir.files().contains(&PathBuf::from("lib.rs")));
+}
+
+#[test]
+fn embed_dir_modules() {
+    embed_dir_modules!(mod crate_files = "$CARGO_MANIFEST_DIR");
+
+    assert_eq!(crate_files::CARGO_TOML.path(), Path::new("Cargo.toml"));
+    assert_eq!(crate_files::src::LIB_RS.path(), Path::new("src/lib.rs"));
+    assert_eq!(
+        crate_files::src::LIB_RS.contents(),
+        include_bytes!("lib.rs")
+    );
+}
+
+#[test]
+fn embedded_hashes() {
+    let plain = anydir!(ct, "$CARGO_MANIFEST_DIR");
+    let hashed = anydir!(ct, "$CARGO_MANIFEST_DIR", hashes = true);
+    let AnyDir::Ct(ct) = &hashed else {
+        unreachable!()
+    };
+    assert_eq!(ct.hashes.unwrap().len(), plain.file_entries().len());
+
+    for (a, b) in plain.file_entries().iter().zip(hashed.file_entries()) {
+        assert_eq!(a.content_hash().unwrap(), b.content_hash().unwrap());
+    }
+}
+
+#[test]
+fn embed_validated() {
+    let dir = anydir!(
+        ct,
+        "$CARGO_MANIFEST_DIR",
+        validate = ["*.toml", "**/*.json"]
+    );
+    assert!(dir.get_file("Cargo.toml").is_some());
+}
+
+#[test]
+fn embed_without_symlinks() {
+    use sha2::Digest;
+
+    // The crate has no symlinks, so skipping them must embed the same tree.
+    let followed = anydir!(ct, "$CARGO_MANIFEST_DIR").file_entries();
+    let skipped = anydir!(ct, "$CARGO_MANIFEST_DIR", follow_symlinks = false).file_entries();
+    assert_eq!(followed.len(), skipped.len());
+    for (a, b) in followed.iter().zip(&skipped) {
+        assert_eq!(a.path(), b.path());
+        assert_eq!(a.read_bytes().unwrap(), b.read_bytes().unwrap());
+    }
+
+    // A link to a file and one to a directory outside the embedded root.
+    let followed = anydir!(
+        ct,
+        "$CARGO_MANIFEST_DIR/../anydir-macro/tests/fixtures/symlinks/root",
+        hashes = true
+    );
+    let skipped = anydir!(
+        ct,
+        "$CARGO_MANIFEST_DIR/../anydir-macro/tests/fixtures/symlinks/root",
+        follow_symlinks = false,
+        hashes = true
+    );
+    followed.assert_paths(&[
+        "real.txt",
+        "link.txt",
+        "linked/bad.json",
+        "linked/inner.txt",
+    ]);
+    skipped.assert_paths(&["real.txt"]);
+    for entry in followed
+        .file_entries()
+        .iter()
+        .chain(&skipped.file_entries())
+    {
+        let hash: [u8; 32] = Sha256::digest(entry.read_bytes().unwrap()).into();
+        assert_eq!(entry.content_hash().unwrap(), hash, "{:?}", entry.path());
+    }
+}
+
+#[test]
+fn file_entries() {
+    let ct = anydir!(ct, "$CARGO_MANIFEST_DIR").file_entries();
+    let rt = anydir!(rt, env!("CARGO_MANIFEST_DIR")).file_entries();
+    let ct_paths: Vec<_> = ct.iter().map(|e| e.path()).collect();
+    let rt_paths: Vec<_> = rt.iter().map(|e| e.path()).collect();
+    assert!(ct_paths.contains(&Path::new("src/lib.rs")));
+    assert_eq!(ct_paths, rt_paths);
+    // Embedded listings are allocated at their exact size up front.
+    assert_eq!(ct.capacity(), ct.len());
+}
+
+#[test]
+fn get_encoded() {
+    let tmp = tempfile::tempdir().unwrap();
+    for name in [
+        "app.js",
+        "app.js.br",
+        "app.js.gz",
+        "style.css",
+        "orphan.js.br",
+    ] {
+        fs::write(tmp.path().join(name), name).unwrap();
+    }
+    let dir = anydir!(rt, tmp.path());
+    let get = |path: &str, accept| {
+        dir.get_encoded(Path::new(path), accept)
+            .map(|(e, enc)| (e.path().to_path_buf(), enc))
+    };
+
+    assert_eq!(
+        get("app.js", "gzip, br"),
+        Some(("app.js.br".into(), Some("br")))
+    );
+    assert_eq!(
+        get("app.js", "gzip, br;q=0.5"),
+        Some(("app.js.gz".into(), Some("gzip")))
+    );
+    assert_eq!(get("app.js", "br;q=0"), Some(("app.js".into(), None)));
+    assert_eq!(get("style.css", "*"), Some(("style.css".into(), None)));
+    assert_eq!(get("missing.js", "br"), None);
+    assert_eq!(get("orphan.js", "br"), None);
+    assert_eq!(get("../app.js", ""), None);
+}
+
+#[test]
+fn single_file_dir() {
+    let tmp = tempfile::tempdir().unwrap();
+    let file = tmp.path().join("config.toml");
+    fs::write(&file, "x = 1").unwrap();
+    let dir = anydir!(rt, &file);
+
+    assert_eq!(dir.files(), [PathBuf::from("config.toml")]);
+    let entries = dir.file_entries();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].path(), Path::new("config.toml"));
+    assert_eq!(entries[0].read_string().unwrap(), "x = 1");
+    assert!(dir.get_file("config.toml").is_some());
+    assert!(dir.get_file("config.toml/config.toml").is_none());
+    assert_eq!(dir.mmap_all().unwrap().len(), 1);
+}
+
+#[test]
+fn cache_listing() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir(tmp.path().join("sub")).unwrap();
+    fs::write(tmp.path().join("a"), "").unwrap();
+    let dir = RtDir::new(tmp.path()).cache_listing(true);
+    assert_eq!(dir.file_entries().len(), 1);
+
+    // Bump the mtime explicitly rather than relying on the filesystem's
+    // timestamp granularity.
+    fs::write(tmp.path().join("sub/b"), "").unwrap();
+    let later = SystemTime::now() + std::time::Duration::from_secs(10);
+    File::open(tmp.path().join("sub"))
+        .unwrap()
+        .set_modified(later)
+        .unwrap();
+    assert_eq!(dir.file_entries().len(), 2);
+
+    // Changes that don't touch any mtime are only seen after invalidating.
+    let root_mtime = fs::metadata(tmp.path()).unwrap().modified().unwrap();
+    fs::remove_file(tmp.path().join("a")).unwrap();
+    File::open(tmp.path())
+        .unwrap()
+        .set_modified(root_mtime)
+        .unwrap();
+    assert_eq!(dir.file_entries().len(), 2);
+    dir.invalidate();
+    assert_eq!(dir.file_entries().len(), 1);
+}
+
+#[test]
+fn entries_with_depth() {
+    let depths: HashMap<_, _> = anydir!(ct, "$CARGO_MANIFEST_DIR")
+        .entries_with_depth()
+        .into_iter()
+        .map(|(e, depth)| (e.path().to_path_buf(), depth))
+        .collect();
+    assert_eq!(depths[Path::new("Cargo.toml")], 0);
+    assert_eq!(depths[Path::new("src/lib.rs")], 1);
+}
+
+#[test]
+fn map_entries() {
+    let dir = anydir!(ct, "$CARGO_MANIFEST_DIR");
+    let lens = dir.map_entries(|e| e.len()).unwrap();
+    assert_eq!(lens.len(), dir.file_entries().len());
+
+    let err = dir
+        .map_entries(|e| e.read_records(0, false).map(|_| ()))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+    #[cfg(feature = "rayon")]
+    assert_eq!(dir.par_map_entries(|e| e.len()).unwrap(), lens);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn par_fold() {
+    let dir = anydir!(ct, "$CARGO_MANIFEST_DIR");
+    let total = dir
+        .par_fold(|| 0, |acc, e| Ok(acc + e.len()?), |a, b| a + b)
+        .unwrap();
+    let expected: u64 = dir.file_entries().iter().map(|e| e.len().unwrap()).sum();
+    assert_eq!(total, expected);
+
+    let err = dir
+        .par_fold(
+            || (),
+            |_, e| e.read_records(0, false).map(|_| ()),
+            |_, _| (),
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn find_by_signature() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("image.dat"), b"\x89PNG\r\n\x1a\nrest").unwrap();
+    fs::write(tmp.path().join("short"), b"\x89P").unwrap();
+    fs::write(tmp.path().join("text.png"), b"not a png").unwrap();
+
+    let found = anydir!(rt, tmp.path())
+        .find_by_signature(b"\x89PNG\r\n\x1a\n")
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].path(), Path::new("image.dat"));
+}
+
+#[cfg(feature = "config")]
+#[test]
+fn read_config_layered() {
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Config {
+        name: String,
+        port: u16,
+        features: Vec<String>,
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(
+        tmp.path().join("defaults.toml"),
+        "name = \"app\"\nport = 80\nfeatures = [\"a\"]\n",
+    )
+    .unwrap();
+    fs::write(tmp.path().join("local.json"), r#"{"port": 8080}"#).unwrap();
+
+    let dir = anydir!(rt, tmp.path());
+    let config: Config = dir
+        .read_config_layered(&[Path::new("defaults.toml"), Path::new("local.json")])
+        .unwrap();
+    assert_eq!(
+        config,
+        Config {
+            name: "app".into(),
+            port: 8080,
+            features: vec!["a".into()],
+        }
+    );
+
+    let missing = dir.read_config_layered::<Config>(&[Path::new("missing.toml")]);
+    assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+    let partial = dir.read_config_layered::<Config>(&[Path::new("local.json")]);
+    assert_eq!(partial.unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "mime")]
+#[test]
+fn entries_of_type() {
+    let mut dir = MemDir::new();
+    for path in [
+        "logo.png",
+        "css/app.css",
+        "photo.JPG",
+        "readme",
+        "data.json",
+    ] {
+        dir.insert(path, &b""[..]);
+    }
+    let paths = |category| -> Vec<_> {
+        dir.entries_of_type(category)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.path().to_path_buf())
+            .collect()
+    };
+    assert_eq!(
+        paths("image"),
+        [Path::new("logo.png"), Path::new("photo.JPG")]
+    );
+    assert_eq!(paths("Text"), [Path::new("css/app.css")]);
+    assert_eq!(paths("application"), [Path::new("data.json")]);
+    assert!(paths("video").is_empty());
+}
+
+#[test]
+fn sibling_dir() {
+    let paths = |dir: AnyDir| -> Vec<PathBuf> {
+        dir.file_entries()
+            .iter()
+            .map(|entry| entry.path().to_path_buf())
+            .collect()
+    };
+
+    // Both backends agree on what's next to a file, paths included.
+    let ct = anydir!(ct, "$CARGO_MANIFEST_DIR");
+    let rt = anydir!(rt, env!("CARGO_MANIFEST_DIR"));
+    for path in ["src/lib.rs", "Cargo.toml"] {
+        let ct_siblings = ct.get_file(path).unwrap().sibling_dir().unwrap();
+        let rt_siblings = rt.get_file(path).unwrap().sibling_dir().unwrap();
+        assert_eq!(paths(ct_siblings), paths(rt_siblings), "{path}");
+    }
+    let siblings = ct.get_file("src/lib.rs").unwrap().sibling_dir().unwrap();
+    assert!(siblings.get_file("Cargo.toml").is_none());
+    let entry = siblings.get_file("entry.rs").unwrap();
+    assert_eq!(entry.path(), Path::new("entry.rs"));
+    assert_eq!(
+        paths(entry.sibling_dir().unwrap()),
+        paths(siblings.get_file("lib.rs").unwrap().sibling_dir().unwrap())
+    );
+
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir_all(tmp.path().join("sub/deep")).unwrap();
+    for name in ["sub/a", "sub/.hidden", "sub/deep/b"] {
+        fs::write(tmp.path().join(name), "").unwrap();
+    }
+    let rt = RtDir::new(tmp.path())
+        .include_hidden(false)
+        .cache_listing(true);
+    let AnyDir::Rt(siblings) = rt.get_file("sub/a").unwrap().sibling_dir().unwrap() else {
+        panic!("not a runtime directory");
+    };
+    let expected = RtDir::new(tmp.path().join("sub"))
+        .include_hidden(false)
+        .cache_listing(true);
+    assert_eq!(siblings, expected);
+    assert_eq!(
+        paths(AnyDir::Rt(siblings)),
+        [Path::new("a"), Path::new("deep/b")]
+    );
+
+    let mut mem = MemDir::new();
+    mem.insert("a", &b""[..]);
+    assert!(mem.get_file("a").unwrap().sibling_dir().is_none());
+}
+
+#[test]
+fn glob() {
+    let mut dir = MemDir::new();
+    for path in [
+        "index.html",
+        "css/app.css",
+        "css/vendor/reset.css",
+        "app.css",
+    ] {
+        dir.insert(path, &b""[..]);
+    }
+    let paths = |entries: Vec<AnyFileEntry>| -> Vec<PathBuf> {
+        entries.iter().map(|e| e.path().to_path_buf()).collect()
+    };
+
+    assert_eq!(paths(dir.glob("*.css").unwrap()), [Path::new("app.css")]);
+    assert_eq!(
+        paths(dir.unmatched_by("**/*.css").unwrap()),
+        [Path::new("index.html")]
+    );
+    for pattern in ["*.css", "css/*", "**/*.css", "*"] {
+        let mut all = paths(dir.glob(pattern).unwrap());
+        all.extend(paths(dir.unmatched_by(pattern).unwrap()));
+        all.sort();
+        assert_eq!(all, paths(dir.file_entries()));
+    }
+    let err = dir.unmatched_by("[").unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn missing_from() {
+    let mut dir = MemDir::new();
+    dir.insert("index.html", &b""[..]);
+    dir.insert("templates/page.html", &b""[..]);
+    dir.insert("templates/old.html", &b""[..]);
+
+    let wanted = [
+        Path::new("templates/page.html"),
+        Path::new("templates/post.html"),
+        Path::new("index.html"),
+        Path::new("templates"),
+    ];
+    assert_eq!(
+        dir.missing_from(&wanted),
+        [Path::new("templates/post.html"), Path::new("templates")]
+    );
+    assert_eq!(dir.extra_beyond(&wanted), [Path::new("templates/old.html")]);
+}
+
+#[test]
+fn assert_paths() {
+    let mut dir = MemDir::new();
+    dir.insert("index.html", &b""[..]);
+    dir.insert("css/app.css", &b""[..]);
+    dir.assert_paths(&["./css/app.css", "index.html"]);
+
+    let panic = std::panic::catch_unwind(|| dir.assert_paths(&["index.html", "app.js"]));
+    let message = *panic.unwrap_err().downcast::<String>().unwrap();
+    assert_eq!(
+        message,
+        "directory paths don't match\n  missing:\n    app.js\n  unexpected:\n    css/app.css"
+    );
+}
+
+#[test]
+fn extract_with_manifest() {
+    let mut dir = MemDir::new();
+    dir.insert("a.txt", &b"alpha"[..]);
+    dir.insert("nested/deep/b.bin", vec![7; 100_000]);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let manifest = dir.extract_with_manifest(tmp.path()).unwrap();
+    assert_eq!(manifest.len(), 2);
+    let extracted = anydir!(rt, tmp.path());
+    for (path, hash) in &manifest {
+        let entry = dir.get_file(path).unwrap();
+        assert_eq!(*hash, entry.content_hash().unwrap());
+        assert_eq!(
+            *hash,
+            extracted.get_file(path).unwrap().content_hash().unwrap()
+        );
+        assert!(entry.extracted_path(tmp.path()).is_file());
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    let options = StreamOptions { buffer_size: 7 };
+    let mut copied = extracted
+        .extract_with_manifest_options(tmp.path(), options)
+        .unwrap();
+    copied.sort();
+    assert_eq!(copied, manifest);
+    let entry = anydir!(rt, tmp.path())
+        .get_file("nested/deep/b.bin")
+        .unwrap();
+    let mut contents = Vec::new();
+    entry
+        .reader_with_options(StreamOptions { buffer_size: 0 })
+        .unwrap()
+        .read_to_end(&mut contents)
+        .unwrap();
+    assert_eq!(contents, vec![7; 100_000]);
+}
+
+#[test]
+fn include_hidden() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir_all(tmp.path().join(".git/objects")).unwrap();
+    fs::create_dir(tmp.path().join("src")).unwrap();
+    fs::write(tmp.path().join(".git/HEAD"), "").unwrap();
+    fs::write(tmp.path().join(".git/objects/ab"), "").unwrap();
+    fs::write(tmp.path().join(".hidden"), "").unwrap();
+    fs::write(tmp.path().join("src/.env"), "").unwrap();
+    fs::write(tmp.path().join("src/main.rs"), "").unwrap();
+    fs::write(tmp.path().join("visible"), "").unwrap();
+
+    let paths = |dir: &RtDir| -> Vec<_> {
+        dir.file_entries()
+            .iter()
+            .map(|entry| entry.path().to_path_buf())
+            .collect()
+    };
+    let all = RtDir::new(tmp.path());
+    assert_eq!(paths(&all).len(), 6);
+    assert!(all.get_file(".git/HEAD").is_some());
+
+    let visible = RtDir::new(tmp.path()).include_hidden(false);
+    assert_eq!(
+        paths(&visible),
+        [Path::new("src/main.rs"), Path::new("visible")]
+    );
+    assert_eq!(visible.files(), [Path::new("visible")]);
+    assert!(visible.get_file(".hidden").is_none());
+    assert!(visible.get_file(".git/HEAD").is_none());
+    assert!(visible.get_file("src/main.rs").is_some());
+}
+
+#[test]
+fn include_hidden_single_file() {
+    let tmp = tempfile::tempdir().unwrap();
+    let file = tmp.path().join(".env");
+    fs::write(&file, "KEY=value").unwrap();
+
+    for include in [true, false] {
+        let dir = RtDir::new(&file).include_hidden(include);
+        let expected = usize::from(include);
+        assert_eq!(dir.file_entries().len(), expected);
+        assert_eq!(dir.files().len(), expected);
+        assert_eq!(dir.get_file(".env").is_some(), include);
+        assert_eq!(
+            dir.state().changes_since(&DirState::default()).len(),
+            expected
+        );
+    }
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn to_multipart() {
+    let mut dir = MemDir::new();
+    dir.insert("css/app.css", &b"body {}"[..]);
+    dir.insert("data", &b"\x00\x01"[..]);
+
+    let (boundary, mut body) = dir.to_multipart().unwrap();
+    assert!(boundary.starts_with("anydir-"));
+    assert_ne!(boundary, dir.to_multipart().unwrap().0);
+    let mut encoded = Vec::new();
+    body.read_to_end(&mut encoded).unwrap();
+    let expected = format!(
+        "--{boundary}\r\n\
+         Content-Disposition: form-data; name=\"css/app.css\"; filename=\"css/app.css\"\r\n\
+         Content-Type: text/css\r\n\r\n\
+         body {{}}\r\n\
+         --{boundary}\r\n\
+         Content-Disposition: form-data; name=\"data\"; filename=\"data\"\r\n\
+         Content-Type: application/octet-stream\r\n\r\n\
+         \x00\x01\r\n\
+         --{boundary}--\r\n"
+    );
+    assert_eq!(encoded, expected.as_bytes());
+}
+
+#[cfg(feature = "mime")]
+#[test]
+fn rich_entries() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("index.html"), "<html>").unwrap();
+    let rich = anydir!(rt, tmp.path()).rich_entries().unwrap();
+    assert_eq!(rich.len(), 1);
+    assert_eq!((rich[0].size, rich[0].mime_type), (6, Some("text/html")));
+    assert!(rich[0].modified.is_some());
+
+    let mut mem = MemDir::new();
+    mem.insert("blob", &b"abc"[..]);
+    let rich = mem.rich_entries().unwrap();
+    assert_eq!((rich[0].size, rich[0].mime_type), (3, None));
+    assert_eq!(rich[0].modified, None);
+
+    #[cfg(feature = "serde")]
+    assert_eq!(
+        serde_json::to_value(&rich[0]).unwrap(),
+        serde_json::json!({ "path": "blob", "size": 3, "mime_type": null, "modified": null })
+    );
+}
+
+#[test]
+fn map_paths() {
+    let mut dir = MemDir::new();
+    dir.insert("b/Logo.PNG", &b"png"[..]);
+    dir.insert("a/App.CSS", &b"css"[..]);
+
+    let lower = dir.map_paths(|path| path.to_string_lossy().to_lowercase().into());
+    let paths: Vec<_> = lower
+        .file_entries()
+        .iter()
+        .map(|entry| entry.path().to_path_buf())
+        .collect();
+    assert_eq!(paths, [Path::new("a/app.css"), Path::new("b/logo.png")]);
+    let logo = lower.get_file("b/logo.png").unwrap();
+    assert_eq!(logo.read_bytes().unwrap(), b"png");
+    assert!(lower.get_file("b/Logo.PNG").is_none());
+
+    let flat = dir.map_paths(|path| path.file_name().unwrap().into());
+    assert!(flat.get_file("App.CSS").is_some());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn non_utf8_names() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let bad = Path::new("sub").join(OsStr::from_bytes(b"caf\xe9.txt"));
+    fs::create_dir(tmp.path().join("sub")).unwrap();
+    fs::write(tmp.path().join(&bad), "latin-1").unwrap();
+    fs::write(tmp.path().join("fine.txt"), "utf-8").unwrap();
+
+    let dir = anydir!(rt, tmp.path());
+    assert_eq!(dir.non_utf8_names(), std::slice::from_ref(&bad));
+    // Nothing that walks the tree trips over the name.
+    let entry = dir.get_file(&bad).unwrap();
+    assert_eq!(entry.read_string().unwrap(), "latin-1");
+    assert_eq!(dir.snapshot().unwrap().len(), 2);
+    assert!(dir.validate_names(NamePolicy::default()).is_empty());
+    assert_eq!(
+        dir.to_blob().unwrap_err().kind(),
+        io::ErrorKind::InvalidData
+    );
+}
+
+#[test]
+fn read_all_strings() {
+    let mut dir = MemDir::new();
+    dir.insert("page.html", &b"<p>hi</p>"[..]);
+    dir.insert("img/logo.png", &b"\x89PNG"[..]);
+
+    let err = dir.read_all_strings(false).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("logo.png"));
+
+    let strings = dir.read_all_strings(true).unwrap();
+    assert_eq!(strings.len(), 1);
+    assert_eq!(strings[Path::new("page.html")], "<p>hi</p>");
+
+    let lossy = dir.read_all_strings_lossy().unwrap();
+    assert_eq!(lossy[Path::new("img/logo.png")], "\u{FFFD}PNG");
+}
+
+#[test]
+fn count_matching() {
+    let is_rs = |entry: &AnyFileEntry| entry.path().extension() == Some(OsStr::new("rs"));
+    let ct = anydir!(ct, "$CARGO_MANIFEST_DIR");
+    let rt = anydir!(rt, env!("CARGO_MANIFEST_DIR"));
+    let expected = ct.file_entries().iter().filter(|e| is_rs(e)).count();
+    assert!(expected > 1);
+    assert_eq!(ct.count_matching(is_rs), expected);
+    assert_eq!(rt.count_matching(is_rs), expected);
+    assert_eq!(ct.count_matching(|_| true), ct.file_entries().len());
+}
+
+#[test]
+fn entries_with_readers() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir(tmp.path().join("sub")).unwrap();
+    fs::write(tmp.path().join("a"), "first").unwrap();
+    fs::write(tmp.path().join("sub/b"), "second").unwrap();
+
+    let dir = anydir!(rt, tmp.path());
+    let mut readers = dir.entries_with_readers().unwrap();
+    // Readers open their file lazily, so a file written after the call is
+    // read with its new contents.
+    fs::write(tmp.path().join("a"), "updated").unwrap();
+
+    let paths: Vec<_> = readers.iter().map(|(path, _)| path.clone()).collect();
+    assert_eq!(paths, [Path::new("a"), Path::new("sub/b")]);
+    let mut contents = String::new();
+    for (_, reader) in &mut readers {
+        reader.read_to_string(&mut contents).unwrap();
+    }
+    assert_eq!(contents, "updatedsecond");
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn read_all_concurrent() {
+    let tmp = tempfile::tempdir().unwrap();
+    for i in 0..20 {
+        fs::write(tmp.path().join(format!("{i:02}")), i.to_string()).unwrap();
+    }
+    let dir = anydir!(rt, tmp.path());
+    let files = dir.read_all_concurrent(3).await.unwrap();
+    assert_eq!(files.len(), 20);
+    for (i, (entry, contents)) in files.iter().enumerate() {
+        assert_eq!(entry.path(), Path::new(&format!("{i:02}")));
+        assert_eq!(contents, i.to_string().as_bytes());
+    }
+    assert!(dir.read_all_concurrent(0).await.is_err());
+
+    let ct = anydir!(ct, "$CARGO_MANIFEST_DIR");
+    let embedded = ct.read_all_concurrent(1).await.unwrap();
+    assert_eq!(embedded.len(), ct.file_entries().len());
+}
+
+#[test]
+fn verify_against() {
+    let tmp = tempfile::tempdir().unwrap();
+    for name in ["same", "tampered", "deleted"] {
+        fs::write(tmp.path().join(name), name).unwrap();
+    }
+    let dir = anydir!(rt, tmp.path());
+    let mut manifest = Vec::new();
+    dir.snapshot().unwrap().write_to(&mut manifest).unwrap();
+    let manifest = Snapshot::read_from(&manifest[..]).unwrap();
+    assert!(dir.verify_against(&manifest).unwrap().is_empty());
+
+    fs::write(tmp.path().join("tampered"), "evil").unwrap();
+    fs::remove_file(tmp.path().join("deleted")).unwrap();
+    fs::write(tmp.path().join("added"), "").unwrap();
+    assert_eq!(
+        dir.verify_against(&manifest).unwrap(),
+        [
+            Path::new("added"),
+            Path::new("deleted"),
+            Path::new("tampered")
+        ]
+    );
+}
+
+#[test]
+fn verify_against_ignores_cached_hashes() {
+    let tmp = tempfile::tempdir().unwrap();
+    let file = tmp.path().join("a");
+    fs::write(&file, "alpha").unwrap();
+    let cached = RtDir::new(tmp.path()).cache_listing(true);
+    let manifest = cached.snapshot().unwrap();
+    assert!(cached.verify_against(&manifest).unwrap().is_empty());
+
+    // Same size and mtime, so only the cached hash would call it unchanged.
+    let modified = fs::metadata(&file).unwrap().modified().unwrap();
+    fs::write(&file, "ALPHA").unwrap();
+    File::options()
+        .write(true)
+        .open(&file)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    assert!(cached.changed_since_snapshot(&manifest).unwrap().is_empty());
+    assert_eq!(cached.verify_against(&manifest).unwrap(), [Path::new("a")]);
+}
+
+#[test]
+fn merge_into_mem() {
+    let mut base = MemDir::new();
+    base.insert("a", &b"base a"[..]);
+    base.insert("shared", &b"one"[..]);
+    let mut extra = MemDir::new();
+    extra.insert("b", &b"extra b"[..]);
+    extra.insert("shared", &b"two"[..]);
+
+    let merged = base
+        .merge_into_mem(&extra, |path, ours, theirs| {
+            assert_eq!(path, Path::new("shared"));
+            [ours, b"+", theirs].concat()
+        })
+        .unwrap();
+    merged.assert_paths(&["a", "b", "shared"]);
+    let read = |path| merged.get_file(path).unwrap().read_bytes().unwrap();
+    assert_eq!(read("a"), b"base a");
+    assert_eq!(read("b"), b"extra b");
+    assert_eq!(read("shared"), b"one+two");
+}
+
+#[test]
+fn tree_hash_reuses_cached_hashes() {
+    let tmp = tempfile::tempdir().unwrap();
+    let file = tmp.path().join("a");
+    fs::write(&file, "alpha").unwrap();
+    let cached = RtDir::new(tmp.path()).cache_listing(true);
+    let first = cached.tree_hash().unwrap();
+
+    // Swaps the contents behind the cache's back: same size and mtime, so
+    // only a re-read could notice.
+    let modified = fs::metadata(&file).unwrap().modified().unwrap();
+    fs::write(&file, "ALPHA").unwrap();
+    File::options()
+        .write(true)
+        .open(&file)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    assert_eq!(cached.tree_hash().unwrap(), first);
+    assert_ne!(RtDir::new(tmp.path()).tree_hash().unwrap(), first);
+}
+
+#[test]
+fn changed_since_snapshot() {
+    let tmp = tempfile::tempdir().unwrap();
+    for name in ["same", "edited", "removed"] {
+        fs::write(tmp.path().join(name), name).unwrap();
+    }
+    let dir = anydir!(rt, tmp.path());
+    let snap = dir.snapshot().unwrap();
+    assert!(dir.changed_since_snapshot(&snap).unwrap().is_empty());
+
+    fs::write(tmp.path().join("edited"), "new contents").unwrap();
+    fs::write(tmp.path().join("added"), "").unwrap();
+    fs::remove_file(tmp.path().join("removed")).unwrap();
+
+    let changed: Vec<_> = dir
+        .changed_since_snapshot(&snap)
+        .unwrap()
+        .into_iter()
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    assert_eq!(changed, [PathBuf::from("added"), "edited".into()]);
+    assert_eq!(
+        dir.removed_since_snapshot(&snap),
+        [PathBuf::from("removed")]
+    );
+}
+
+#[test]
+fn entries_by_mtime() {
+    use std::time::{Duration, SystemTime};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let now = SystemTime::now();
+    for (name, age) in [("a", 30), ("b", 10), ("c", 20)] {
+        let file = File::create(tmp.path().join(name)).unwrap();
+        file.set_modified(now - Duration::from_secs(age)).unwrap();
+    }
+    let paths = |entries: Vec<AnyFileEntry>| -> Vec<_> {
+        entries.iter().map(|e| e.path().to_path_buf()).collect()
+    };
+    let dir = anydir!(rt, tmp.path());
+    assert_eq!(
+        paths(dir.entries_by_mtime(true).unwrap()),
+        [Path::new("b"), Path::new("c"), Path::new("a")]
+    );
+    assert_eq!(
+        paths(dir.entries_by_mtime(false).unwrap()),
+        [Path::new("a"), Path::new("c"), Path::new("b")]
+    );
+
+    let ct = anydir!(ct, "$CARGO_MANIFEST_DIR");
+    assert_eq!(
+        paths(ct.entries_by_mtime(true).unwrap()),
+        paths(ct.file_entries())
+    );
+}
+
+#[test]
+fn etag_map() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a"), "alpha").unwrap();
+    let rt = anydir!(rt, tmp.path()).etag_map().unwrap();
+    assert!(rt[Path::new("a")].starts_with("W/\"5-"));
+
+    let ct = anydir!(ct, "$CARGO_MANIFEST_DIR");
+    let tags = ct.etag_map().unwrap();
+    assert_eq!(tags.len(), ct.file_entries().len());
+    let entry = ct.get_file("Cargo.toml").unwrap();
+    assert_eq!(
+        tags[Path::new("Cargo.toml")],
+        format!("\"{}\"", snapshot::hex(&entry.content_hash().unwrap()))
+    );
+}
+
+#[test]
+fn profile_reads() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a"), "alpha").unwrap();
+    fs::write(tmp.path().join("b"), "beta").unwrap();
+    let dir = anydir!(rt, tmp.path());
+
+    let profile = dir.profile_reads().unwrap();
+    let paths: Vec<_> = profile.iter().map(|(e, _)| e.path()).collect();
+    assert_eq!(paths, [Path::new("a"), Path::new("b")]);
+}
+
+#[test]
+fn largest_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    for (name, len) in [("a", 3), ("b", 10), ("c", 1), ("d", 7)] {
+        fs::write(tmp.path().join(name), vec![0; len]).unwrap();
+    }
+    let dir = anydir!(rt, tmp.path());
+
+    let largest: Vec<_> = dir
+        .largest_files(2)
+        .unwrap()
+        .into_iter()
+        .map(|(e, len)| (e.path().to_path_buf(), len))
+        .collect();
+    assert_eq!(largest, [("b".into(), 10), ("d".into(), 7)]);
+    assert_eq!(dir.largest_files(10).unwrap().len(), 4);
+    assert!(dir.largest_files(0).unwrap().is_empty());
+}
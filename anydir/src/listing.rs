@@ -0,0 +1,66 @@
+use crate::AnyFileEntry;
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+/// The cached result of walking an [`RtDir`](crate::RtDir), shared between
+/// clones of it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ListingCache(Arc<RwLock<Option<Listing>>>);
+
+#[derive(Debug)]
+struct Listing {
+    entries: Vec<AnyFileEntry>,
+    /// Every directory walked to produce `entries`, with its mtime at the
+    /// time.
+    dirs: Vec<(PathBuf, SystemTime)>,
+}
+
+impl Listing {
+    fn is_fresh(&self) -> bool {
+        self.dirs.iter().all(|(dir, modified)| {
+            fs::metadata(dir)
+                .and_then(|m| m.modified())
+                .is_ok_and(|m| m == *modified)
+        })
+    }
+}
+
+impl ListingCache {
+    /// Returns the cached entries if they're still fresh, otherwise rescans
+    /// with `scan`, which records the directories it walked into its
+    /// argument.
+    pub(crate) fn get_or_scan(
+        &self,
+        scan: impl FnOnce(&mut Vec<(PathBuf, SystemTime)>) -> Vec<AnyFileEntry>,
+    ) -> Vec<AnyFileEntry> {
+        if let Some(listing) = &*self.0.read().unwrap_or_else(|e| e.into_inner()) {
+            if listing.is_fresh() {
+                return listing.entries.clone();
+            }
+        }
+
+        let mut dirs = Vec::new();
+        let entries = scan(&mut dirs);
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = Some(Listing {
+            entries: entries.clone(),
+            dirs,
+        });
+        entries
+    }
+
+    pub(crate) fn clear(&self) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+}
+
+// Whether a listing happens to be cached doesn't change which directory an
+// `RtDir` refers to.
+impl PartialEq for ListingCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
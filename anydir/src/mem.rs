@@ -0,0 +1,278 @@
+use crate::{
+    entry::{invalid_utf8, is_contained, is_normal, parse_slash_path, HashCache},
+    AnyFileEntry, DirOps, FileEntry, MappedFile, SeekableRead,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// Identifies a blob written by [`DirOps::to_blob`].
+const BLOB_MAGIC: &[u8; 8] = b"ANYDIR\0\0";
+const BLOB_VERSION: u32 = 1;
+
+/// A directory held entirely in memory.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MemDir {
+    /// Each file's contents, with the hash cache its entries share.
+    files: BTreeMap<PathBuf, (Arc<[u8]>, HashCache)>,
+}
+
+impl MemDir {
+    pub fn new() -> Self {
+        MemDir::default()
+    }
+
+    /// Adds a file at the relative `path`, returning the contents it
+    /// replaced, if any.
+    ///
+    /// # Panics
+    ///
+    /// If `path` is empty or isn't a plain relative path, i.e. it's absolute
+    /// or contains `.` or `..`.
+    #[track_caller]
+    pub fn insert<P: Into<PathBuf>, C: Into<Arc<[u8]>>>(
+        &mut self,
+        path: P,
+        contents: C,
+    ) -> Option<Arc<[u8]>> {
+        let path = path.into();
+        assert!(is_normal(&path), "invalid MemDir path {path:?}");
+        self.files
+            .insert(path, (contents.into(), HashCache::default()))
+            .map(|(contents, _)| contents)
+    }
+
+    /// Reconstructs a directory from a blob written by [`DirOps::to_blob`].
+    ///
+    /// A blob consists of:
+    ///
+    /// - the magic bytes `ANYDIR\0\0`
+    /// - the format version, currently `1`, as a little-endian `u32`
+    /// - the number of files as a little-endian `u64`
+    /// - for each file, the length of its path as a little-endian `u32`, the
+    ///   path as UTF-8 with `/` separators, the length of its contents as a
+    ///   little-endian `u64`, then the contents
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the blob is truncated,
+    /// was written by an unsupported format version, or holds a path that is
+    /// empty, duplicated or would escape the directory.
+    pub fn from_blob(blob: &[u8]) -> io::Result<MemDir> {
+        let mut reader = blob;
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic).map_err(truncated)?;
+        if &magic != BLOB_MAGIC {
+            return Err(invalid("not an anydir blob"));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != BLOB_VERSION {
+            return Err(invalid(&format!("unsupported blob version {version}")));
+        }
+
+        let mut dir = MemDir::new();
+        for _ in 0..read_u64(&mut reader)? {
+            let path_len = read_u32(&mut reader)?;
+            let path = read_chunk(&mut reader, path_len.into())?;
+            let path = String::from_utf8(path.to_vec()).map_err(|_| invalid("non-UTF-8 path"))?;
+            let path = parse_slash_path(&path)
+                .ok_or_else(|| invalid(&format!("invalid path {path:?}")))?;
+            let contents_len = read_u64(&mut reader)?;
+            let contents = read_chunk(&mut reader, contents_len)?;
+            if dir.insert(&path, contents).is_some() {
+                return Err(invalid(&format!("duplicate path {}", path.display())));
+            }
+        }
+        if !reader.is_empty() {
+            return Err(invalid("trailing data after the last file"));
+        }
+        Ok(dir)
+    }
+
+    /// Writes `entries` in the format read by [`MemDir::from_blob`].
+    pub(crate) fn write_blob(entries: &[AnyFileEntry]) -> io::Result<Vec<u8>> {
+        let mut blob = Vec::new();
+        blob.extend_from_slice(BLOB_MAGIC);
+        blob.extend_from_slice(&BLOB_VERSION.to_le_bytes());
+        blob.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        for entry in entries {
+            let path = entry.path();
+            let components: Option<Vec<_>> = path.iter().map(|c| c.to_str()).collect();
+            let path = components.ok_or_else(|| invalid_utf8(path))?.join("/");
+            let path_len = u32::try_from(path.len()).map_err(|_| invalid("path too long"))?;
+            blob.extend_from_slice(&path_len.to_le_bytes());
+            blob.extend_from_slice(path.as_bytes());
+
+            let contents = entry.read_bytes()?;
+            blob.extend_from_slice(&(contents.len() as u64).to_le_bytes());
+            blob.extend_from_slice(&contents);
+        }
+        Ok(blob)
+    }
+
+    fn entry(&self, path: &Path, (contents, hash): &(Arc<[u8]>, HashCache)) -> AnyFileEntry {
+        AnyFileEntry::Mem(MemFileEntry {
+            path: path.to_path_buf(),
+            contents: contents.clone(),
+            hash: hash.clone(),
+        })
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid blob: {msg}"))
+}
+
+fn truncated(_: io::Error) -> io::Error {
+    invalid("unexpected end of data")
+}
+
+fn read_u32(reader: &mut &[u8]) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf).map_err(truncated)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(reader: &mut &[u8]) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    reader.read_exact(&mut buf).map_err(truncated)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_chunk<'a>(reader: &mut &'a [u8], len: u64) -> io::Result<&'a [u8]> {
+    let len = usize::try_from(len).map_err(|_| invalid("unexpected end of data"))?;
+    if reader.len() < len {
+        return Err(invalid("unexpected end of data"));
+    }
+    let (chunk, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(chunk)
+}
+
+impl DirOps for MemDir {
+    fn files(&self) -> Vec<PathBuf> {
+        self.files
+            .keys()
+            .filter(|path| path.components().count() == 1)
+            .cloned()
+            .collect()
+    }
+
+    fn file_entries(&self) -> Vec<AnyFileEntry> {
+        self.files
+            .iter()
+            .map(|(path, file)| self.entry(path, file))
+            .collect()
+    }
+
+    fn get_file<P: AsRef<Path>>(&self, path: P) -> Option<AnyFileEntry> {
+        let path = path.as_ref();
+        if !is_contained(path) {
+            return None;
+        }
+        let file = self.files.get(path)?;
+        Some(self.entry(path, file))
+    }
+
+    fn mmap_all(&self) -> io::Result<HashMap<PathBuf, MappedFile>> {
+        Ok(self
+            .files()
+            .into_iter()
+            .map(|path| {
+                let contents = self.files[&path].0.clone();
+                (path, MappedFile::Shared(contents))
+            })
+            .collect())
+    }
+}
+
+/// A file in a [`MemDir`]. Its contents are shared with the directory, not
+/// copied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemFileEntry {
+    pub(crate) path: PathBuf,
+    pub(crate) contents: Arc<[u8]>,
+    hash: HashCache,
+}
+
+impl FileEntry for MemFileEntry {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.contents.len() as u64)
+    }
+
+    fn size_hint(&self) -> Option<u64> {
+        Some(self.contents.len() as u64)
+    }
+
+    fn read_bytes_into(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        buf.clear();
+        buf.extend_from_slice(&self.contents);
+        Ok(buf.len())
+    }
+
+    fn read_string_into(&self, buf: &mut String) -> io::Result<usize> {
+        let contents = std::str::from_utf8(&self.contents).map_err(|_| invalid_utf8(&self.path))?;
+        buf.clear();
+        buf.push_str(contents);
+        Ok(buf.len())
+    }
+
+    fn reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(Box::new(&self.contents[..]))
+    }
+
+    fn seekable(&self) -> io::Result<Box<dyn SeekableRead + '_>> {
+        Ok(Box::new(io::Cursor::new(&self.contents[..])))
+    }
+
+    fn cached_hash(&self) -> io::Result<[u8; 32]> {
+        self.hash.get_or_compute(|| self.content_hash())
+    }
+}
+
+#[test]
+fn blob_round_trip() {
+    let dir = crate::anydir!(ct, "$CARGO_MANIFEST_DIR");
+    let blob = dir.to_blob().unwrap();
+    let mem = MemDir::from_blob(&blob).unwrap();
+    assert_eq!(mem.files(), dir.files());
+    for (a, b) in dir.file_entries().iter().zip(mem.file_entries()) {
+        assert_eq!(a.path(), b.path());
+        assert_eq!(a.read_bytes().unwrap(), b.read_bytes().unwrap());
+    }
+
+    assert!(MemDir::from_blob(&blob[..blob.len() - 1]).is_err());
+    assert!(MemDir::from_blob(b"ANYDIR\0\0\x02\0\0\0").is_err());
+
+    let blob_with_path = |path: &str| {
+        let mut blob = b"ANYDIR\0\0\x01\0\0\0\x01\0\0\0\0\0\0\0".to_vec();
+        blob.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        blob.extend_from_slice(path.as_bytes());
+        blob.extend_from_slice(&0u64.to_le_bytes());
+        blob
+    };
+    assert!(MemDir::from_blob(&blob_with_path("a/b")).is_ok());
+    for path in ["", "../x", "/etc/passwd", "a//b", "./a"] {
+        let err = MemDir::from_blob(&blob_with_path(path)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{path:?}");
+    }
+}
+
+#[test]
+fn invalid_paths() {
+    let mut dir = MemDir::new();
+    dir.insert("a/b", &b""[..]);
+    assert!(dir.get_file("a/b").is_some());
+    assert!(dir.get_file("a/../a/b").is_none());
+    assert!(dir.get_file("/a/b").is_none());
+
+    for path in ["", "../x", "/etc/passwd", "a/../b", "./a"] {
+        let inserted = std::panic::catch_unwind(|| MemDir::new().insert(path, &b""[..]));
+        assert!(inserted.is_err(), "{path:?}");
+    }
+}
@@ -0,0 +1,48 @@
+use crate::{read::Concat, snapshot::hex, AnyFileEntry, FileEntry};
+use sha2::{Digest, Sha256};
+use std::{
+    io::{Cursor, Read},
+    sync::atomic::{AtomicU64, Ordering},
+    time::SystemTime,
+};
+
+/// A boundary unlikely to appear in any file, different on every call.
+fn boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = Sha256::new();
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    hasher.update(now.as_nanos().to_le_bytes());
+    hasher.update(COUNTER.fetch_add(1, Ordering::Relaxed).to_le_bytes());
+    hasher.update(std::process::id().to_le_bytes());
+    format!("anydir-{}", &hex(&hasher.finalize())[..32])
+}
+
+/// Escapes a field name or file name for a `Content-Disposition` header
+/// the way browsers do.
+fn escape(name: &str) -> String {
+    name.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+pub(crate) fn encode(entries: Vec<AnyFileEntry>) -> (String, Concat) {
+    let boundary = boundary();
+    let mut parts: Vec<Box<dyn Read + Send>> = Vec::new();
+    for entry in entries {
+        let path: Vec<_> = entry.path().iter().map(|c| c.to_string_lossy()).collect();
+        let name = escape(&path.join("/"));
+        let mime = entry.mime_type().unwrap_or("application/octet-stream");
+        let header = format!(
+            "--{boundary}\r\n\
+             Content-Disposition: form-data; name=\"{name}\"; filename=\"{name}\"\r\n\
+             Content-Type: {mime}\r\n\r\n"
+        );
+        parts.push(Box::new(Cursor::new(header)));
+        parts.push(entry.into_reader());
+        parts.push(Box::new(&b"\r\n"[..]));
+    }
+    parts.push(Box::new(Cursor::new(format!("--{boundary}--\r\n"))));
+    (boundary, Concat::new(parts))
+}
@@ -0,0 +1,142 @@
+use crate::{AnyFileEntry, FileEntry};
+use std::{collections::HashMap, path::PathBuf};
+
+/// Which checks [`DirOps::validate_names`](crate::DirOps::validate_names)
+/// runs. The default enables all of them, with Windows' traditional
+/// 260-character path limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamePolicy {
+    /// Flag names Windows reserves for devices, like `CON` or `nul.txt`.
+    pub reserved_names: bool,
+    /// Flag names containing ASCII control characters.
+    pub control_chars: bool,
+    /// Flag relative paths longer than this many UTF-16 code units.
+    pub max_path_len: Option<usize>,
+    /// Flag paths that only differ from another by case, which collide on
+    /// case-insensitive filesystems.
+    pub case_collisions: bool,
+}
+
+impl Default for NamePolicy {
+    fn default() -> Self {
+        NamePolicy {
+            reserved_names: true,
+            control_chars: true,
+            max_path_len: Some(260),
+            case_collisions: true,
+        }
+    }
+}
+
+/// A portability problem with a file's path, found by
+/// [`DirOps::validate_names`](crate::DirOps::validate_names).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameViolation {
+    /// A path component is a reserved Windows device name.
+    ReservedName(String),
+    /// A path component contains an ASCII control character.
+    ControlChar(char),
+    /// The path is `len` UTF-16 code units long, over the policy's limit.
+    TooLong { len: usize },
+    /// The path only differs by case from `other`, which sorts before it.
+    CaseCollision { other: PathBuf },
+}
+
+const RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+pub(crate) fn validate(
+    entries: &[AnyFileEntry],
+    policy: &NamePolicy,
+) -> Vec<(PathBuf, NameViolation)> {
+    let mut violations = Vec::new();
+    let mut seen = HashMap::new();
+    for entry in entries {
+        let path = entry.path();
+        let mut report = |violation| violations.push((path.to_path_buf(), violation));
+
+        for component in path.iter() {
+            let name = component.to_string_lossy();
+            if policy.reserved_names {
+                // Windows ignores everything after the first dot, and
+                // trailing spaces, when checking for device names.
+                let stem = name.split('.').next().unwrap_or("").trim_end();
+                if RESERVED.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+                    report(NameViolation::ReservedName(name.to_string()));
+                }
+            }
+            if policy.control_chars {
+                if let Some(c) = name.chars().find(char::is_ascii_control) {
+                    report(NameViolation::ControlChar(c));
+                }
+            }
+        }
+
+        let lossy = path.to_string_lossy();
+        if let Some(max) = policy.max_path_len {
+            let len = lossy.encode_utf16().count();
+            if len > max {
+                report(NameViolation::TooLong { len });
+            }
+        }
+        if policy.case_collisions {
+            if let Some(other) = seen.insert(lossy.to_lowercase(), path) {
+                report(NameViolation::CaseCollision {
+                    other: other.to_path_buf(),
+                });
+            }
+        }
+    }
+    violations
+}
+
+#[test]
+fn validate_names() {
+    use crate::{DirOps, MemDir};
+
+    let mut dir = MemDir::new();
+    for path in [
+        "ok.txt",
+        "Readme",
+        "README",
+        "aux/x",
+        "nul.tar.gz",
+        "tab\there",
+    ] {
+        dir.insert(path, &b""[..]);
+    }
+    dir.insert("long/".repeat(60), &b""[..]);
+
+    let violations = dir.validate_names(NamePolicy::default());
+    let find = |path: &str| -> Vec<_> {
+        violations
+            .iter()
+            .filter(|(p, _)| p == &PathBuf::from(path))
+            .map(|(_, v)| v.clone())
+            .collect()
+    };
+    assert_eq!(find("ok.txt"), []);
+    assert_eq!(find("README"), []);
+    assert_eq!(
+        find("Readme"),
+        [NameViolation::CaseCollision {
+            other: "README".into()
+        }]
+    );
+    assert_eq!(find("aux/x"), [NameViolation::ReservedName("aux".into())]);
+    assert_eq!(
+        find("nul.tar.gz"),
+        [NameViolation::ReservedName("nul.tar.gz".into())]
+    );
+    assert_eq!(find("tab\there"), [NameViolation::ControlChar('\t')]);
+    assert_eq!(violations.len(), 5);
+
+    let lenient = NamePolicy {
+        max_path_len: None,
+        case_collisions: false,
+        ..NamePolicy::default()
+    };
+    assert_eq!(dir.validate_names(lenient).len(), 3);
+}
@@ -0,0 +1,210 @@
+use sha2::digest::{Digest, Output};
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, Write},
+    path::PathBuf,
+};
+
+/// A reader that can also seek, returned by
+/// [`FileEntry::seekable`](crate::FileEntry::seekable). Implemented for
+/// everything that is both [`Read`] and [`Seek`].
+pub trait SeekableRead: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> SeekableRead for T {}
+
+/// Iterator over the fixed-size records of a file, returned by
+/// [`FileEntry::read_records`](crate::FileEntry::read_records).
+pub struct Records<'a> {
+    reader: Box<dyn Read + 'a>,
+    record_size: usize,
+    allow_partial: bool,
+    done: bool,
+}
+
+impl<'a> Records<'a> {
+    pub(crate) fn new(reader: Box<dyn Read + 'a>, record_size: usize, allow_partial: bool) -> Self {
+        Records {
+            reader,
+            record_size,
+            allow_partial,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Records<'_> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut record = Vec::with_capacity(self.record_size);
+        let result = (&mut self.reader)
+            .take(self.record_size as u64)
+            .read_to_end(&mut record);
+        match result {
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+            Ok(0) => {
+                self.done = true;
+                None
+            }
+            Ok(n) if n < self.record_size => {
+                self.done = true;
+                if self.allow_partial {
+                    Some(Ok(record))
+                } else {
+                    Some(Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        format!(
+                            "trailing partial record of {n} bytes (expected {})",
+                            self.record_size
+                        ),
+                    )))
+                }
+            }
+            Ok(_) => Some(Ok(record)),
+        }
+    }
+}
+
+/// Hashes everything written through it on the way to `inner`.
+pub(crate) struct HashingWriter<W, D> {
+    inner: W,
+    hasher: D,
+}
+
+impl<W: Write, D: Digest> HashingWriter<W, D> {
+    pub(crate) fn new(inner: W) -> Self {
+        HashingWriter {
+            inner,
+            hasher: D::new(),
+        }
+    }
+
+    pub(crate) fn finish(self) -> (W, Output<D>) {
+        (self.inner, self.hasher.finalize())
+    }
+}
+
+impl<W: Write, D: Digest> Write for HashingWriter<W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Tuning for operations that stream file contents rather than reading them
+/// whole, like [`FileEntry::reader_with_options`](crate::FileEntry::reader_with_options)
+/// and [`DirOps::extract_with_manifest_options`](crate::DirOps::extract_with_manifest_options).
+///
+/// # Buffer size
+///
+/// `buffer_size` is how many bytes are read from disk, and copied, per
+/// chunk. The default is 8 KiB, the same as [`BufReader`]. Bigger buffers
+/// (say 1 MiB) mean fewer system calls and noticeably higher throughput on
+/// large files, at the cost of holding that much memory per open reader.
+/// Smaller ones suit memory-constrained targets or many concurrent streams.
+/// A size of zero is treated as one byte.
+///
+/// Compile-time and in-memory files are already in memory, so the buffer
+/// size only affects how they're copied, never how they're read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamOptions {
+    pub buffer_size: usize,
+}
+
+impl StreamOptions {
+    pub(crate) fn buffer_size(&self) -> usize {
+        self.buffer_size.max(1)
+    }
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        StreamOptions {
+            buffer_size: 8 * 1024,
+        }
+    }
+}
+
+/// Copies `reader` to the end into `writer` in chunks of
+/// `options.buffer_size`, returning the number of bytes copied.
+pub(crate) fn copy(
+    reader: &mut dyn Read,
+    writer: &mut dyn Write,
+    options: StreamOptions,
+) -> io::Result<u64> {
+    let mut buf = vec![0; options.buffer_size()];
+    let mut copied = 0;
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok(copied),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..n])?;
+        copied += n as u64;
+    }
+}
+
+/// Reads a file that isn't opened until the first call to `read`.
+pub(crate) struct LazyFile {
+    path: PathBuf,
+    file: Option<BufReader<File>>,
+}
+
+impl LazyFile {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        LazyFile { path, file: None }
+    }
+}
+
+impl Read for LazyFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let file = match &mut self.file {
+            Some(file) => file,
+            None => self.file.insert(BufReader::new(File::open(&self.path)?)),
+        };
+        file.read(buf)
+    }
+}
+
+/// Reads each of `parts` to the end in turn.
+#[cfg(feature = "http")]
+pub(crate) struct Concat {
+    parts: std::collections::VecDeque<Box<dyn Read + Send>>,
+}
+
+#[cfg(feature = "http")]
+impl Concat {
+    pub(crate) fn new(parts: Vec<Box<dyn Read + Send>>) -> Self {
+        Concat {
+            parts: parts.into(),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl Read for Concat {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(part) = self.parts.front_mut() {
+            match part.read(buf)? {
+                0 if !buf.is_empty() => {
+                    self.parts.pop_front();
+                }
+                n => return Ok(n),
+            }
+        }
+        Ok(0)
+    }
+}
@@ -0,0 +1,56 @@
+use crate::{AnyFileEntry, FileEntry};
+use std::{fs, io, time::SystemTime};
+
+/// A file with the details a directory listing shows, gathered by
+/// [`DirOps::rich_entries`](crate::DirOps::rich_entries).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RichEntry {
+    pub entry: AnyFileEntry,
+    /// The file's size in bytes.
+    pub size: u64,
+    /// The MIME type guessed from the file's extension.
+    pub mime_type: Option<&'static str>,
+    /// When the file was last modified. Only runtime files record this.
+    pub modified: Option<SystemTime>,
+}
+
+impl RichEntry {
+    pub(crate) fn new(entry: AnyFileEntry) -> io::Result<Self> {
+        let (size, modified) = match &entry {
+            // One `stat` covers both.
+            AnyFileEntry::Rt(e) => {
+                let metadata = fs::metadata(&e.full_path)?;
+                (metadata.len(), metadata.modified().ok())
+            }
+            _ => (entry.len()?, None),
+        };
+        Ok(RichEntry {
+            mime_type: entry.mime_type(),
+            entry,
+            size,
+            modified,
+        })
+    }
+}
+
+/// Serializes as `{ path, size, mime_type, modified }`, with the path using
+/// `/` separators and `modified` in serde's usual `SystemTime` form.
+#[cfg(feature = "serde")]
+impl serde::Serialize for RichEntry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let path: Vec<_> = self
+            .entry
+            .path()
+            .iter()
+            .map(|c| c.to_string_lossy())
+            .collect();
+        let mut s = serializer.serialize_struct("RichEntry", 4)?;
+        s.serialize_field("path", &path.join("/"))?;
+        s.serialize_field("size", &self.size)?;
+        s.serialize_field("mime_type", &self.mime_type)?;
+        s.serialize_field("modified", &self.modified)?;
+        s.end()
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{entry, DirOps, FileEntry};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+/// The SHA-256 content hash of every file in a directory at some point in
+/// time, taken with [`DirOps::snapshot`].
+///
+/// Snapshots can be persisted with [`Snapshot::write_to`] and loaded back
+/// with [`Snapshot::read_from`], so a build tool can compare a directory
+/// against its state in a previous run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    hashes: BTreeMap<PathBuf, [u8; 32]>,
+}
+
+impl Snapshot {
+    pub(crate) fn capture<D: DirOps + ?Sized>(dir: &D) -> io::Result<Self> {
+        let mut hashes = BTreeMap::new();
+        for entry in dir.file_entries() {
+            hashes.insert(entry.path().to_path_buf(), entry.cached_hash()?);
+        }
+        Ok(Snapshot { hashes })
+    }
+
+    /// The hash recorded for `path`, if it was in the directory.
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<&[u8; 32]> {
+        self.hashes.get(path.as_ref())
+    }
+
+    /// Iterates over the recorded paths and hashes, sorted by path.
+    pub fn iter(&self) -> impl Iterator<Item = (&Path, &[u8; 32])> {
+        self.hashes
+            .iter()
+            .map(|(path, hash)| (path.as_path(), hash))
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes.is_empty()
+    }
+
+    /// A Merkle hash of the whole tree: each directory hashes the names and
+    /// hashes of its children, so two snapshots share a tree hash exactly
+    /// when they record the same paths with the same contents.
+    pub fn tree_hash(&self) -> [u8; 32] {
+        let files: Vec<(Vec<&OsStr>, &[u8; 32])> = self
+            .hashes
+            .iter()
+            .map(|(path, hash)| (path.iter().collect(), hash))
+            .collect();
+        merkle(&files, 0)
+    }
+
+    /// Writes the snapshot in the format used by `sha256sum`: one
+    /// `<hex hash>  <path>` line per file.
+    ///
+    /// Paths are written with `/` separators on every platform. Paths that
+    /// aren't valid UTF-8 are written lossily and won't match on reload.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for (path, hash) in &self.hashes {
+            let path: Vec<_> = path.iter().map(|c| c.to_string_lossy()).collect();
+            writeln!(writer, "{}  {}", hex(hash), path.join("/"))?;
+        }
+        Ok(())
+    }
+
+    /// Reads a snapshot written by [`Snapshot::write_to`].
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] on a line that isn't a
+    /// hash and a path, or whose path is empty or could point outside the
+    /// directory (absolute, or containing `.` or `..`).
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut hashes = BTreeMap::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = || {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid snapshot line: {line:?}"),
+                )
+            };
+            let (hash, path) = line.split_once("  ").ok_or_else(invalid)?;
+            let hash = parse_hex(hash).ok_or_else(invalid)?;
+            let path = entry::parse_slash_path(path).ok_or_else(invalid)?;
+            hashes.insert(path, hash);
+        }
+        Ok(Snapshot { hashes })
+    }
+}
+
+/// Hashes the directory holding `files`, which are sorted by path and share
+/// their first `depth` components.
+fn merkle(files: &[(Vec<&OsStr>, &[u8; 32])], depth: usize) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    let mut rest = files;
+    while let Some(((components, hash), tail)) = rest.split_first() {
+        // A path that ends here has no name to hash under; only an empty
+        // path at the root can get this far.
+        let Some(&name) = components.get(depth) else {
+            rest = tail;
+            continue;
+        };
+        let (child, kind, len) = if components.len() == depth + 1 {
+            (**hash, b'f', 1)
+        } else {
+            // Stops before a file with the same name as the directory, so
+            // neither swallows the other.
+            let len = rest
+                .iter()
+                .take_while(|(c, _)| c.len() > depth + 1 && c.get(depth) == Some(&name))
+                .count();
+            (merkle(&rest[..len], depth + 1), b'd', len)
+        };
+        let name = name.as_encoded_bytes();
+        hasher.update([kind]);
+        hasher.update((name.len() as u64).to_le_bytes());
+        hasher.update(name);
+        hasher.update(child);
+        rest = &rest[len..];
+    }
+    hasher.finalize().into()
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn parse_hex(s: &str) -> Option<[u8; 32]> {
+    if s.len() != 64 || !s.is_ascii() {
+        return None;
+    }
+    let mut out = [0; 32];
+    for (byte, chunk) in out.iter_mut().zip(s.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+    }
+    Some(out)
+}
+
+#[test]
+fn round_trip() {
+    let dir = crate::anydir!(ct, "$CARGO_MANIFEST_DIR");
+    let snapshot = dir.snapshot().unwrap();
+    assert_eq!(snapshot.len(), dir.file_entries().len());
+
+    let mut buf = Vec::new();
+    snapshot.write_to(&mut buf).unwrap();
+    assert_eq!(Snapshot::read_from(&buf[..]).unwrap(), snapshot);
+    assert!(Snapshot::read_from(&b"nothex  a.txt\n"[..]).is_err());
+}
+
+#[test]
+fn malformed_paths() {
+    let hash = hex(&[0; 32]);
+    for path in [
+        "",
+        "/etc/passwd",
+        "a//b.txt",
+        "../a.txt",
+        "a/../../b",
+        "./a.txt",
+    ] {
+        let line = format!("{hash}  {path}\n");
+        let err = Snapshot::read_from(line.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{path:?}");
+    }
+    let line = format!("{hash}  a/b.txt\n");
+    let snapshot = Snapshot::read_from(line.as_bytes()).unwrap();
+    assert!(snapshot.get("a/b.txt").is_some());
+}
+
+#[test]
+fn tree_hash() {
+    use crate::MemDir;
+
+    let mut a = MemDir::new();
+    a.insert("x/y", &b"1"[..]);
+    a.insert("z", &b"2"[..]);
+    let mut b = a.clone();
+    assert!(a.same_tree_as(&b).unwrap());
+
+    b.insert("z", &b"3"[..]);
+    assert!(!a.same_tree_as(&b).unwrap());
+
+    // Moving a file changes the hash even when the contents don't.
+    let mut c = MemDir::new();
+    c.insert("x/y", &b"1"[..]);
+    c.insert("x/z", &b"2"[..]);
+    assert_ne!(a.tree_hash().unwrap(), c.tree_hash().unwrap());
+    assert_ne!(MemDir::new().tree_hash().unwrap(), c.tree_hash().unwrap());
+}
+
+#[test]
+fn tree_hash_odd_paths() {
+    let snapshot = |paths: &[&str]| Snapshot {
+        hashes: paths.iter().map(|&p| (p.into(), [0; 32])).collect(),
+    };
+    assert_eq!(snapshot(&[""]).tree_hash(), Snapshot::default().tree_hash());
+    assert_eq!(
+        snapshot(&["", "a"]).tree_hash(),
+        snapshot(&["a"]).tree_hash()
+    );
+
+    // A file and a directory with the same name each count.
+    let both = snapshot(&["a", "a/b"]).tree_hash();
+    assert_ne!(both, snapshot(&["a"]).tree_hash());
+    assert_ne!(both, snapshot(&["a/b"]).tree_hash());
+}
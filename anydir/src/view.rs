@@ -0,0 +1,65 @@
+use crate::{entry::is_contained, AnyFileEntry, DirOps, FileEntry, MappedFile};
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+/// A view of a directory with every relative path rewritten, returned by
+/// [`DirOps::map_paths`].
+///
+/// Paths are mapped on every call rather than once up front, so the view
+/// sees changes to a runtime directory. [`DirOps::files`] and
+/// [`DirOps::mmap_all`] cover the underlying directory's top-level files,
+/// renamed. [`DirOps::get_file`] can't invert an arbitrary mapping, so it
+/// scans [`DirOps::file_entries`] for the first file mapped to the path it's
+/// given.
+pub struct MappedPaths<'a, D: ?Sized, F> {
+    dir: &'a D,
+    map: F,
+}
+
+impl<'a, D: DirOps + ?Sized, F: Fn(&Path) -> PathBuf> MappedPaths<'a, D, F> {
+    pub(crate) fn new(dir: &'a D, map: F) -> Self {
+        MappedPaths { dir, map }
+    }
+}
+
+impl<D: DirOps + ?Sized, F: Fn(&Path) -> PathBuf> DirOps for MappedPaths<'_, D, F> {
+    fn files(&self) -> Vec<PathBuf> {
+        self.dir
+            .files()
+            .iter()
+            .map(|path| (self.map)(path))
+            .collect()
+    }
+
+    fn mmap_all(&self) -> io::Result<HashMap<PathBuf, MappedFile>> {
+        Ok(self
+            .dir
+            .mmap_all()?
+            .into_iter()
+            .map(|(path, file)| ((self.map)(&path), file))
+            .collect())
+    }
+
+    fn file_entries(&self) -> Vec<AnyFileEntry> {
+        let mut entries = self.dir.file_entries();
+        for entry in &mut entries {
+            let mapped = (self.map)(entry.path());
+            *entry.path_mut() = mapped;
+        }
+        entries.sort_by(|a, b| a.path().cmp(b.path()));
+        entries
+    }
+
+    fn get_file<P: AsRef<Path>>(&self, path: P) -> Option<AnyFileEntry> {
+        let path = path.as_ref();
+        if !is_contained(path) {
+            return None;
+        }
+        self.file_entries()
+            .into_iter()
+            .find(|entry| entry.path() == path)
+    }
+}
@@ -0,0 +1,201 @@
+use crate::RtDir;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Whether a path is a file or a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathKind {
+    File,
+    Dir,
+}
+
+/// One difference between two [`DirState`]s, as returned by
+/// [`DirState::changes_since`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirChange {
+    Created(PathBuf, PathKind),
+    Removed(PathBuf, PathKind),
+    /// A file whose size or modification time changed.
+    Modified(PathBuf),
+    /// A path that's now the other kind, e.g. a file that was deleted and
+    /// had a directory created in its place. Anything inside a directory on
+    /// either side is reported separately, as created or removed.
+    KindChanged {
+        path: PathBuf,
+        from: PathKind,
+        to: PathKind,
+    },
+}
+
+/// Every file and directory under an [`RtDir`] at one point in time, taken
+/// with [`RtDir::state`]. Comparing two of them with
+/// [`DirState::changes_since`] tells what changed in between, which is
+/// enough to keep a model of the directory up to date by polling.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirState {
+    /// Each path with its kind, and for files their size and mtime.
+    paths: BTreeMap<PathBuf, (PathKind, Option<(u64, SystemTime)>)>,
+}
+
+impl DirState {
+    /// What changed between `earlier` and this state, sorted by path.
+    pub fn changes_since(&self, earlier: &DirState) -> Vec<DirChange> {
+        let mut changes = Vec::new();
+        for (path, &(kind, stamp)) in &self.paths {
+            match earlier.paths.get(path) {
+                None => changes.push(DirChange::Created(path.clone(), kind)),
+                Some(&(from, _)) if from != kind => changes.push(DirChange::KindChanged {
+                    path: path.clone(),
+                    from,
+                    to: kind,
+                }),
+                Some(&(_, earlier_stamp)) if earlier_stamp != stamp => {
+                    changes.push(DirChange::Modified(path.clone()))
+                }
+                Some(_) => {}
+            }
+        }
+        for (path, &(kind, _)) in &earlier.paths {
+            if !self.paths.contains_key(path) {
+                changes.push(DirChange::Removed(path.clone(), kind));
+            }
+        }
+        changes.sort_by(|a, b| a.path().cmp(b.path()));
+        changes
+    }
+}
+
+impl DirChange {
+    /// The path that changed, relative to the directory.
+    pub fn path(&self) -> &Path {
+        match self {
+            DirChange::Created(path, _)
+            | DirChange::Removed(path, _)
+            | DirChange::Modified(path)
+            | DirChange::KindChanged { path, .. } => path,
+        }
+    }
+}
+
+impl RtDir {
+    /// Records every file and directory in the tree, skipping the same
+    /// entries [`DirOps::file_entries`](crate::DirOps::file_entries) does,
+    /// symlinks included.
+    pub fn state(&self) -> DirState {
+        fn stamp(path: &Path) -> Option<(u64, SystemTime)> {
+            let meta = fs::metadata(path).ok()?;
+            Some((meta.len(), meta.modified().ok()?))
+        }
+
+        fn walk(rt: &RtDir, dir: &Path, state: &mut DirState) {
+            let Ok(entries) = fs::read_dir(dir) else {
+                return;
+            };
+            for entry in entries.flatten() {
+                if rt.is_excluded(&entry.file_name()) {
+                    continue;
+                }
+                let full_path = entry.path();
+                let path = full_path
+                    .strip_prefix(&rt.dir)
+                    .unwrap_or(&full_path)
+                    .to_path_buf();
+                match entry.file_type() {
+                    Ok(ft) if ft.is_dir() => {
+                        state.paths.insert(path, (PathKind::Dir, None));
+                        walk(rt, &full_path, state);
+                    }
+                    Ok(ft) if ft.is_file() => {
+                        state
+                            .paths
+                            .insert(path, (PathKind::File, stamp(&full_path)));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let mut state = DirState::default();
+        match self.single_file() {
+            Some(name) if !self.is_excluded(name) => {
+                state
+                    .paths
+                    .insert(name.into(), (PathKind::File, stamp(&self.dir)));
+            }
+            Some(_) => {}
+            None => walk(self, &self.dir, &mut state),
+        }
+        state
+    }
+}
+
+#[test]
+fn kind_changes() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::write(tmp.path().join("a"), "file").unwrap();
+    fs::write(tmp.path().join("kept"), "kept").unwrap();
+    let dir = RtDir::new(tmp.path());
+    let before = dir.state();
+    assert!(dir.state().changes_since(&before).is_empty());
+
+    fs::remove_file(tmp.path().join("a")).unwrap();
+    fs::create_dir(tmp.path().join("a")).unwrap();
+    fs::write(tmp.path().join("a/b"), "inside").unwrap();
+    let after = dir.state();
+    assert_eq!(
+        after.changes_since(&before),
+        [
+            DirChange::KindChanged {
+                path: "a".into(),
+                from: PathKind::File,
+                to: PathKind::Dir,
+            },
+            DirChange::Created("a/b".into(), PathKind::File),
+        ]
+    );
+
+    fs::remove_dir_all(tmp.path().join("a")).unwrap();
+    fs::write(tmp.path().join("a"), "file again").unwrap();
+    fs::write(tmp.path().join("kept"), "kept, but longer").unwrap();
+    assert_eq!(
+        dir.state().changes_since(&after),
+        [
+            DirChange::KindChanged {
+                path: "a".into(),
+                from: PathKind::Dir,
+                to: PathKind::File,
+            },
+            DirChange::Removed("a/b".into(), PathKind::File),
+            DirChange::Modified("kept".into()),
+        ]
+    );
+    assert_eq!(
+        before.changes_since(&dir.state()),
+        [
+            DirChange::Modified("a".into()),
+            DirChange::Modified("kept".into()),
+        ]
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_cycle() {
+    let tmp = tempfile::tempdir().unwrap();
+    fs::create_dir(tmp.path().join("sub")).unwrap();
+    fs::write(tmp.path().join("sub/a"), "a").unwrap();
+    std::os::unix::fs::symlink(".", tmp.path().join("sub/loop")).unwrap();
+    std::os::unix::fs::symlink("a", tmp.path().join("sub/link")).unwrap();
+    let state = RtDir::new(tmp.path()).state();
+    assert_eq!(
+        state.changes_since(&DirState::default()),
+        [
+            DirChange::Created("sub".into(), PathKind::Dir),
+            DirChange::Created("sub/a".into(), PathKind::File),
+        ]
+    );
+}
@@ -0,0 +1,479 @@
+use crate::{
+    entry::{invalid_utf8, HashCache},
+    AnyFileEntry, DirOps, FileEntry, MappedFile, SeekableRead,
+};
+use flate2::read::DeflateDecoder;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use zip::{CompressionMethod, ZipArchive};
+
+type Source = Arc<Mutex<Box<dyn SeekableRead + Send>>>;
+type Archive = ZipArchive<Shared>;
+
+/// A handle on the archive's underlying reader, shared by the
+/// [`ZipArchive`] and every member being streamed out of it. Each handle
+/// keeps its own position and seeks back to it before every read, so
+/// readers of different members can be interleaved.
+struct Shared {
+    source: Source,
+    pos: u64,
+}
+
+impl Read for Shared {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut source = self.source.lock().unwrap_or_else(|e| e.into_inner());
+        if source.stream_position()? != self.pos {
+            source.seek(SeekFrom::Start(self.pos))?;
+        }
+        let read = source.read(buf)?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for Shared {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let mut source = self.source.lock().unwrap_or_else(|e| e.into_inner());
+        source.seek(SeekFrom::Start(self.pos))?;
+        self.pos = source.seek(pos)?;
+        Ok(self.pos)
+    }
+}
+
+/// Checks a streamed member against the CRC-32 in its header once the
+/// whole of it has been read, as `zip` does for the members it reads.
+struct Checked<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+    expected: u32,
+}
+
+impl<R: Read> Read for Checked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        if read == 0 && !buf.is_empty() && self.hasher.clone().finalize() != self.expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "zip member doesn't match its checksum",
+            ));
+        }
+        Ok(read)
+    }
+}
+
+/// The most that's reserved up front for a member's contents. Sizes come
+/// from the archive itself, so a forged one mustn't decide how much memory
+/// is allocated before anything has been decompressed.
+const MAX_RESERVE: u64 = 1024 * 1024;
+
+/// A zip archive used as a directory without extracting it.
+///
+/// The archive's central directory is read once, when it's opened, so
+/// listing and looking up files never touches the members themselves. A
+/// member is only decompressed when its entry is read. [`FileEntry::reader`]
+/// streams it, but [`FileEntry::seekable`] decompresses it into memory
+/// first, since a compressed stream can't seek. Clones share one handle on
+/// the archive, so reads from them take turns.
+#[derive(Clone)]
+pub struct ZipDir {
+    source: Source,
+    archive: Arc<Mutex<Archive>>,
+    /// Each member's index in the archive and its uncompressed size, with
+    /// the hash cache its entries share.
+    files: Arc<BTreeMap<PathBuf, (usize, u64, HashCache)>>,
+}
+
+impl ZipDir {
+    /// Opens the zip archive at `path`.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidData`] if the file isn't a zip
+    /// archive or holds a member whose name would escape the directory.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<ZipDir> {
+        ZipDir::new(Box::new(BufReader::new(File::open(path)?)))
+    }
+
+    /// Reads a zip archive embedded in the binary, e.g. with
+    /// `include_bytes!`. Fails like [`ZipDir::open`].
+    pub fn from_static(bytes: &'static [u8]) -> io::Result<ZipDir> {
+        ZipDir::new(Box::new(io::Cursor::new(bytes)))
+    }
+
+    fn new(reader: Box<dyn SeekableRead + Send>) -> io::Result<ZipDir> {
+        let source = Arc::new(Mutex::new(reader));
+        let mut archive = ZipArchive::new(Shared {
+            source: source.clone(),
+            pos: 0,
+        })?;
+        let mut files = BTreeMap::new();
+        for index in 0..archive.len() {
+            let member = archive.by_index_raw(index)?;
+            if member.is_dir() {
+                continue;
+            }
+            let path = member.enclosed_name().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("zip member {} is outside the directory", member.name()),
+                )
+            })?;
+            files.insert(resolve(&path), (index, member.size(), HashCache::default()));
+        }
+        Ok(ZipDir {
+            source,
+            archive: Arc::new(Mutex::new(archive)),
+            files: Arc::new(files),
+        })
+    }
+
+    fn entry(&self, path: &Path, (index, size, hash): &(usize, u64, HashCache)) -> AnyFileEntry {
+        AnyFileEntry::Zip(ZipFileEntry {
+            path: path.to_path_buf(),
+            source: self.source.clone(),
+            archive: self.archive.clone(),
+            index: *index,
+            size: *size,
+            hash: hash.clone(),
+        })
+    }
+}
+
+/// Drops the `.` and `..` components that [`zip::read::ZipFile::enclosed_name`]
+/// lets through, as long as they stay inside the archive.
+fn resolve(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => resolved.push(name),
+            _ => {}
+        }
+    }
+    resolved
+}
+
+impl fmt::Debug for ZipDir {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZipDir")
+            .field("files", &self.files.keys())
+            .finish_non_exhaustive()
+    }
+}
+
+impl DirOps for ZipDir {
+    fn files(&self) -> Vec<PathBuf> {
+        self.files
+            .keys()
+            .filter(|path| path.components().count() == 1)
+            .cloned()
+            .collect()
+    }
+
+    fn file_entries(&self) -> Vec<AnyFileEntry> {
+        self.files
+            .iter()
+            .map(|(path, member)| self.entry(path, member))
+            .collect()
+    }
+
+    fn get_file<P: AsRef<Path>>(&self, path: P) -> Option<AnyFileEntry> {
+        let (path, member) = self.files.get_key_value(path.as_ref())?;
+        Some(self.entry(path, member))
+    }
+
+    /// Zip members can't be mapped, so this decompresses every top-level
+    /// file into memory.
+    fn mmap_all(&self) -> io::Result<HashMap<PathBuf, MappedFile>> {
+        self.files()
+            .into_iter()
+            .map(|path| {
+                let contents = self.get_file(&path).unwrap().read_bytes()?;
+                Ok((path, MappedFile::Shared(contents.into())))
+            })
+            .collect()
+    }
+}
+
+/// A file in a [`ZipDir`], decompressed whenever it's read.
+#[derive(Clone)]
+pub struct ZipFileEntry {
+    pub(crate) path: PathBuf,
+    source: Source,
+    archive: Arc<Mutex<Archive>>,
+    index: usize,
+    size: u64,
+    hash: HashCache,
+}
+
+impl ZipFileEntry {
+    /// Turns the entry into a reader that owns everything it needs. The
+    /// member isn't opened until the first read.
+    pub(crate) fn into_reader(self) -> Box<dyn Read + Send> {
+        struct Lazy(ZipFileEntry, Option<Box<dyn Read + Send>>);
+
+        impl Read for Lazy {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                let stream = match &mut self.1 {
+                    Some(stream) => stream,
+                    None => self.1.insert(self.0.stream()?),
+                };
+                stream.read(buf)
+            }
+        }
+
+        Box::new(Lazy(self, None))
+    }
+
+    /// Decompresses the member as it's read, straight from its stretch of
+    /// the archive. Members `zip` can't be streamed from this way, such as
+    /// encrypted ones, are read in full instead.
+    fn stream(&self) -> io::Result<Box<dyn Read + Send>> {
+        let streamable = {
+            let mut archive = self.archive.lock().unwrap_or_else(|e| e.into_inner());
+            let member = archive.by_index_raw(self.index)?;
+            let supported = matches!(
+                member.compression(),
+                CompressionMethod::Stored | CompressionMethod::Deflated
+            );
+            (supported && !member.encrypted()).then(|| {
+                (
+                    member.data_start(),
+                    member.compressed_size(),
+                    member.compression(),
+                    member.crc32(),
+                )
+            })
+        };
+        let Some((start, len, compression, crc)) = streamable else {
+            return Ok(Box::new(io::Cursor::new(self.read_bytes()?)));
+        };
+        let raw = Shared {
+            source: self.source.clone(),
+            pos: start,
+        }
+        .take(len);
+        let inner: Box<dyn Read + Send> = if compression == CompressionMethod::Deflated {
+            Box::new(DeflateDecoder::new(raw))
+        } else {
+            Box::new(raw)
+        };
+        Ok(Box::new(Checked {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            expected: crc,
+        }))
+    }
+}
+
+impl fmt::Debug for ZipFileEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZipFileEntry")
+            .field("path", &self.path)
+            .field("index", &self.index)
+            .field("size", &self.size)
+            .finish_non_exhaustive()
+    }
+}
+
+// Entries are the same if they're the same member of the same open archive.
+impl PartialEq for ZipFileEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.path == other.path
+            && self.index == other.index
+            && Arc::ptr_eq(&self.archive, &other.archive)
+    }
+}
+
+impl FileEntry for ZipFileEntry {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn len(&self) -> io::Result<u64> {
+        Ok(self.size)
+    }
+
+    fn read_bytes_into(&self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        buf.clear();
+        buf.reserve(self.size.min(MAX_RESERVE) as usize);
+        // Every read seeks to its member afresh, so a panic mid-read
+        // doesn't leave the archive in a state later reads would trip on.
+        let mut archive = self.archive.lock().unwrap_or_else(|e| e.into_inner());
+        let read = archive.by_index(self.index)?.read_to_end(buf);
+        read
+    }
+
+    fn read_string_into(&self, buf: &mut String) -> io::Result<usize> {
+        let bytes = self.read_bytes()?;
+        *buf = String::from_utf8(bytes).map_err(|_| invalid_utf8(&self.path))?;
+        Ok(buf.len())
+    }
+
+    fn reader(&self) -> io::Result<Box<dyn Read + '_>> {
+        Ok(self.stream()?)
+    }
+
+    fn seekable(&self) -> io::Result<Box<dyn SeekableRead + '_>> {
+        Ok(Box::new(io::Cursor::new(self.read_bytes()?)))
+    }
+
+    fn cached_hash(&self) -> io::Result<[u8; 32]> {
+        self.hash.get_or_compute(|| self.content_hash())
+    }
+}
+
+#[test]
+fn zip_dir() {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    writer.add_directory("css", options).unwrap();
+    for (name, contents) in [
+        ("index.html", "<html>"),
+        ("css/app.css", "body {}"),
+        ("css/../about.html", "about"),
+    ] {
+        writer.start_file(name, options).unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let tmp = tempfile::tempdir().unwrap();
+    std::fs::write(tmp.path().join("site.zip"), &bytes).unwrap();
+    let dirs = [
+        ZipDir::open(tmp.path().join("site.zip")).unwrap(),
+        ZipDir::from_static(bytes.leak()).unwrap(),
+    ];
+    for dir in dirs {
+        dir.assert_paths(&["index.html", "about.html", "css/app.css"]);
+        assert_eq!(
+            dir.files(),
+            [Path::new("about.html"), Path::new("index.html")]
+        );
+        let entry = dir.get_file("css/app.css").unwrap();
+        assert_eq!(entry.len().unwrap(), 7);
+        assert_eq!(entry.read_string().unwrap(), "body {}");
+        assert_eq!(entry.read_head(4).unwrap(), b"body");
+        assert!(dir.get_file("css").is_none());
+        assert!(dir.get_file("../index.html").is_none());
+    }
+
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    writer
+        .start_file("../evil", SimpleFileOptions::default())
+        .unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+    let err = ZipDir::from_static(bytes.leak()).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn forged_size() {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .large_file(true);
+    writer.start_file("big", options).unwrap();
+    writer.write_all(b"tiny").unwrap();
+    let mut bytes = writer.finish().unwrap().into_inner();
+
+    // Claim an uncompressed size of 8 EiB in the central directory's zip64
+    // extra field, which directly follows the 46-byte header and the name.
+    let header = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    let extra = header + 46 + "big".len();
+    assert_eq!(bytes[extra..extra + 2], [1, 0]);
+    bytes[extra + 4..extra + 12].copy_from_slice(&(u64::MAX / 2).to_le_bytes());
+
+    let dir = ZipDir::from_static(bytes.leak()).unwrap();
+    let entry = dir.get_file("big").unwrap();
+    assert_eq!(entry.len().unwrap(), u64::MAX / 2);
+    assert_eq!(entry.read_bytes().unwrap(), b"tiny");
+}
+
+#[test]
+fn streamed_members() {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let big: Vec<u8> = (0..200_000u32).flat_map(|i| i.to_le_bytes()).collect();
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    for (name, method) in [
+        ("deflated", CompressionMethod::Deflated),
+        ("stored", CompressionMethod::Stored),
+    ] {
+        let options = SimpleFileOptions::default().compression_method(method);
+        writer.start_file(name, options).unwrap();
+        writer.write_all(&big).unwrap();
+    }
+    let mut bytes = writer.finish().unwrap().into_inner();
+
+    // Reading two members in turn shows neither holds on to the archive.
+    let dir = ZipDir::from_static(bytes.clone().leak()).unwrap();
+    let deflated = dir.get_file("deflated").unwrap();
+    let stored = dir.get_file("stored").unwrap();
+    let (mut a, mut b) = (deflated.reader().unwrap(), stored.reader().unwrap());
+    let (mut from_a, mut from_b) = (Vec::new(), Vec::new());
+    let mut chunk = [0; 4096];
+    loop {
+        let read_a = a.read(&mut chunk).unwrap();
+        from_a.extend_from_slice(&chunk[..read_a]);
+        let read_b = b.read(&mut chunk).unwrap();
+        from_b.extend_from_slice(&chunk[..read_b]);
+        if read_a == 0 && read_b == 0 {
+            break;
+        }
+    }
+    assert!(from_a == big && from_b == big);
+    assert_eq!(deflated.read_head(8).unwrap(), big[..8]);
+
+    // Corrupting the last byte of the stored member only shows up once
+    // the whole of it has been read.
+    let end = bytes.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+    bytes[end - 1] ^= 1;
+    let dir = ZipDir::from_static(bytes.leak()).unwrap();
+    let stored = dir.get_file("stored").unwrap();
+    assert_eq!(stored.read_head(8).unwrap(), big[..8]);
+    let err = stored.reader().unwrap().read_to_end(&mut Vec::new());
+    assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn read_vectored_async() {
+    use io::IoSliceMut;
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    writer.start_file("file", options).unwrap();
+    writer.write_all(b"deflated").unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    // The decoder fills one buffer per read, so it takes several.
+    let entry = ZipDir::from_static(bytes.leak())
+        .unwrap()
+        .get_file("file")
+        .unwrap();
+    let (mut a, mut b, mut c) = ([0; 3], [0; 4], [b'-'; 4]);
+    let mut bufs = [
+        IoSliceMut::new(&mut a),
+        IoSliceMut::new(&mut b),
+        IoSliceMut::new(&mut c),
+    ];
+    assert_eq!(entry.read_vectored_async(&mut bufs).await.unwrap(), 8);
+    assert_eq!((&a, &b, &c), (b"def", b"late", b"d---"));
+}
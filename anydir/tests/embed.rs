@@ -0,0 +1,49 @@
+/// Points the UI tests' `$ANYDIR_FIXTURES` at the macro crate's fixtures,
+/// which trybuild's generated crate can't otherwise find. They live there so
+/// the tests here that embed this whole crate don't pick them up.
+fn set_fixtures() {
+    let fixtures = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../anydir-macro/tests/fixtures"
+    );
+    std::env::set_var("ANYDIR_FIXTURES", fixtures);
+}
+
+#[test]
+fn required_files() {
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/require_present.rs");
+    t.compile_fail("tests/ui/require_missing.rs");
+}
+
+#[test]
+fn relative_dirs() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/relative_missing.rs");
+}
+
+#[test]
+fn module_names() {
+    set_fixtures();
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/modules_names.rs");
+    t.compile_fail("tests/ui/modules_collision.rs");
+}
+
+#[test]
+fn validated_files() {
+    set_fixtures();
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/validate_valid.rs");
+    t.compile_fail("tests/ui/validate_json.rs");
+    t.compile_fail("tests/ui/validate_toml.rs");
+}
+
+#[test]
+fn skipped_symlinks() {
+    set_fixtures();
+    let t = trybuild::TestCases::new();
+    t.pass("tests/ui/symlinks_skipped.rs");
+    t.compile_fail("tests/ui/symlinks_required.rs");
+    t.compile_fail("tests/ui/symlinks_validated.rs");
+}
@@ -0,0 +1,3 @@
+anydir::embed_dir_modules!(mod assets = "$ANYDIR_FIXTURES/collision");
+
+fn main() {}
@@ -0,0 +1,20 @@
+use anydir::{anydir, embed_dir_modules};
+
+// A local item named like the crate mustn't capture the generated paths.
+#[allow(dead_code)]
+mod include_dir {}
+
+embed_dir_modules!(mod assets = "$ANYDIR_FIXTURES/modules");
+
+fn main() {
+    // Names that aren't identifiers, or start with a digit or are keywords,
+    // are sanitized.
+    assert_eq!(assets::_1ST_TXT.contents(), b"first");
+    assert_eq!(assets::A_B_CSS.contents(), b"a {}");
+    assert_eq!(assets::mod_::X_TXT.path().to_str(), Some("mod/x.txt"));
+    assert_eq!(assets::css::APP_CSS.contents(), b"body {}");
+
+    let unlinked = anydir!(ct, "$ANYDIR_FIXTURES/modules", follow_symlinks = false);
+    assert!(unlinked.get_file("css/app.css").is_some());
+    let _dir = anydir!(ct, "$ANYDIR_FIXTURES/modules");
+}
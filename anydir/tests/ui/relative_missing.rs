@@ -0,0 +1,6 @@
+use anydir::embed_dir_relative;
+
+fn main() {
+    // `src` is next to the manifest, not next to this file.
+    let _dir = embed_dir_relative!("src");
+}
@@ -0,0 +1,5 @@
+use anydir::anydir;
+
+fn main() {
+    let _dir = anydir!(ct, "$CARGO_MANIFEST_DIR", require = ["Cargo.toml", "index.html"]);
+}
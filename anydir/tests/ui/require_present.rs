@@ -0,0 +1,8 @@
+use anydir::anydir;
+
+// trybuild compiles this in a generated crate, so `$CARGO_MANIFEST_DIR` is
+// that crate's directory, which always holds a `Cargo.toml`.
+fn main() {
+    let dir = anydir!(ct, "$CARGO_MANIFEST_DIR", require = ["Cargo.toml"]);
+    assert!(dir.get_file("Cargo.toml").is_some());
+}
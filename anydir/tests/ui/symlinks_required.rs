@@ -0,0 +1,16 @@
+use anydir::anydir;
+
+fn main() {
+    let _dir = anydir!(
+        ct,
+        "$ANYDIR_FIXTURES/symlinks/root",
+        follow_symlinks = false,
+        require = ["link.txt"]
+    );
+    let _dir = anydir!(
+        ct,
+        "$ANYDIR_FIXTURES/symlinks/root",
+        follow_symlinks = false,
+        require = ["linked/inner.txt"]
+    );
+}
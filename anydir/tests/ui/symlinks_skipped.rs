@@ -0,0 +1,17 @@
+use anydir::anydir;
+
+// `linked/bad.json` isn't valid JSON and `link.txt` isn't required, so this
+// only compiles if neither link is followed.
+fn main() {
+    let dir = anydir!(
+        ct,
+        "$ANYDIR_FIXTURES/symlinks/root",
+        follow_symlinks = false,
+        hashes = true,
+        validate = "**/*.json",
+        require = ["real.txt"]
+    );
+    assert!(dir.get_file("real.txt").is_some());
+    assert!(dir.get_file("link.txt").is_none());
+    assert!(dir.get_file("linked/inner.txt").is_none());
+}
@@ -0,0 +1,6 @@
+use anydir::anydir;
+
+// Followed by default, so the file behind the directory link is validated.
+fn main() {
+    let _dir = anydir!(ct, "$ANYDIR_FIXTURES/symlinks/root", validate = "**/*.json");
+}
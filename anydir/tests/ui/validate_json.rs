@@ -0,0 +1,5 @@
+use anydir::anydir;
+
+fn main() {
+    let _dir = anydir!(ct, "$ANYDIR_FIXTURES/invalid", validate = "*.json");
+}
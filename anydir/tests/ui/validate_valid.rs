@@ -0,0 +1,6 @@
+use anydir::anydir;
+
+fn main() {
+    let dir = anydir!(ct, "$ANYDIR_FIXTURES/valid", validate = ["*.json", "**/*.toml"]);
+    assert!(dir.get_file("config/app.toml").is_some());
+}